@@ -0,0 +1,245 @@
+//! Interpreting on an async runtime, over tokio's [AsyncRead] and [AsyncWrite]. Only built with the `async` feature.
+
+use crate::brainfuck_runtime_error::BrainfuckRuntimeError;
+use crate::cell_kind::CellKind;
+use crate::input_queue::InputQueue;
+use crate::step_status::StepStatus;
+use crate::VM;
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// How many steps [VM::interpret_async] runs before yielding to the async runtime, so that a long running program
+/// does not keep other tasks from running.
+const YIELD_INTERVAL: usize = 4096;
+
+impl<T> VM<'_, T>
+where
+    T: CellKind,
+{
+    /// Interpret the borrowed [ValidatedProgram](bf_types::ValidatedProgram) like [VM::interpret], but awaiting input
+    /// and output instead of blocking on them, and yielding to the async runtime every few thousand steps.
+    /// # Example
+    /// ```no_run
+    /// use bf_types::*;
+    /// use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Program::from_file("./hello_world.bf")?.validated()?;
+    /// let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(30000).unwrap(), true, &program);
+    /// let mut output = vec![];
+    /// virtual_machine
+    ///     .interpret_async(&mut tokio::io::empty(), &mut output)
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn interpret_async<R, W>(
+        &mut self,
+        read_source: &mut R,
+        write_destination: &mut W,
+    ) -> Result<(), BrainfuckRuntimeError>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let mut input = InputQueue::new();
+        let mut output = AsyncOutput::default();
+        let result = loop {
+            let status = self
+                .run_for(YIELD_INTERVAL, &mut input, &mut output, write_destination)
+                .await;
+            match status {
+                Ok(StepStatus::Running) => tokio::task::yield_now().await,
+                Ok(StepStatus::NeedsInput) => {
+                    let read =
+                        self.read_async(read_source, &mut input, &mut output, write_destination);
+                    if let Err(e) = read.await {
+                        break Err(e);
+                    }
+                }
+                Ok(StepStatus::Halted) => break Ok(()),
+                Ok(StepStatus::Error(e)) | Err(e) => break Err(e),
+            }
+        };
+
+        // Write whatever is left and end with a newline like [VM::interpret] does, any failure is blamed on the last
+        // output instruction unless something failed already.
+        let finished = output.finish(write_destination).await;
+        let finished = finished.map_err(|e| self.output_error(e));
+        result.and(finished).map_err(|e| self.with_state(e))
+    }
+
+    /// Run at most `steps` steps on `input`, writing output to `write_destination` whenever the flush policy asks for
+    /// it. Stops early on anything else than a [StepStatus::Running].
+    async fn run_for<W: AsyncWrite + Unpin>(
+        &mut self,
+        steps: usize,
+        input: &mut InputQueue,
+        output: &mut AsyncOutput,
+        write_destination: &mut W,
+    ) -> Result<StepStatus, BrainfuckRuntimeError> {
+        for _ in 0..steps {
+            let written = output.pending.len();
+            match self.step(input, &mut output.pending) {
+                StepStatus::Running => {}
+                status => return Ok(status),
+            }
+            if output.pending.len() > written
+                && output
+                    .pending
+                    .last()
+                    .is_some_and(|&value| self.flush_policy.flush_after(value))
+            {
+                output
+                    .write(write_destination, true)
+                    .await
+                    .map_err(|e| self.output_error(e))?;
+            }
+        }
+        output
+            .write(write_destination, false)
+            .await
+            .map_err(|e| self.output_error(e))?;
+        Ok(StepStatus::Running)
+    }
+
+    /// Await one more byte of input and push it to `input`, closing it once `read_source` has no more. The output
+    /// written so far is flushed first if the flush policy asks for it, so that prompts are shown.
+    async fn read_async<R, W>(
+        &mut self,
+        read_source: &mut R,
+        input: &mut InputQueue,
+        output: &mut AsyncOutput,
+        write_destination: &mut W,
+    ) -> Result<(), BrainfuckRuntimeError>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let flush = self.flush_policy.flush_before_input();
+        let written = output.write(write_destination, flush).await;
+        written.map_err(|e| {
+            BrainfuckRuntimeError::CannotWriteOutputError(
+                e,
+                self.program.file_path().to_owned(),
+                self.source_instruction(),
+            )
+        })?;
+
+        let mut buf = [0; 1];
+        match read_source.read(&mut buf).await {
+            Ok(0) => input.close(),
+            Ok(_) => input.push(&buf),
+            Err(e) => {
+                return Err(BrainfuckRuntimeError::CannotReadInputError(
+                    e,
+                    self.program.file_path().to_owned(),
+                    self.source_instruction(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Blame a failure to write output on the last output instruction which ran.
+    fn output_error(&self, error: io::Error) -> BrainfuckRuntimeError {
+        let idx = self.last_output.unwrap_or_default();
+        BrainfuckRuntimeError::CannotWriteOutputError(
+            error,
+            self.program.file_path().to_owned(),
+            self.program.instructions()[idx],
+        )
+    }
+}
+
+/// Output of [VM::interpret_async] waiting to be written.
+#[derive(Debug, Default)]
+struct AsyncOutput {
+    /// Written by the [VM] but not to the write destination yet.
+    pending: Vec<u8>,
+
+    /// If the last byte written to the write destination is a newline.
+    ends_with_newline: bool,
+}
+
+impl AsyncOutput {
+    /// Write pending output to `write_destination`, and flush it if asked to.
+    async fn write<W: AsyncWrite + Unpin>(
+        &mut self,
+        write_destination: &mut W,
+        flush: bool,
+    ) -> io::Result<()> {
+        if let Some(&last) = self.pending.last() {
+            write_destination.write_all(&self.pending).await?;
+            self.ends_with_newline = last == b'\n';
+            self.pending.clear();
+        }
+        if flush {
+            write_destination.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Write pending output and a newline if the output does not already end with one, then flush everything.
+    async fn finish<W: AsyncWrite + Unpin>(&mut self, write_destination: &mut W) -> io::Result<()> {
+        self.write(write_destination, false).await?;
+        if !self.ends_with_newline {
+            self.pending.push(b'\n');
+        }
+        self.write(write_destination, true).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eof_policy::EofPolicy;
+    use crate::flush_policy::FlushPolicy;
+    use bf_types::pass_manager::{OptLevel, PassManager};
+    use bf_types::{Instruction, Program, RawInstruction};
+    use std::num::NonZeroUsize;
+
+    /// Should read input and write output like [VM::interpret] does, including the final newline.
+    #[tokio::test]
+    async fn interpret_async() {
+        let program = Program::new("", ",[.,]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), false, &program)
+            .with_eof_policy(EofPolicy::Zero)
+            .with_flush_policy(FlushPolicy::Newline);
+        let mut output = vec![];
+        virtual_machine
+            .interpret_async(&mut &b"hello"[..], &mut output)
+            .await
+            .unwrap();
+
+        assert_eq!(output, b"hello\n");
+    }
+
+    /// Should let other tasks run while interpreting a long running program, and fail at the same place as
+    /// [VM::interpret] does.
+    #[tokio::test]
+    async fn yield_to_other_tasks() {
+        let program = Program::new(
+            "",
+            "++[>+++++++++++[>+++++++++++[>+++++++++++++[-]<-]<-]<-]<<",
+        )
+        .validated()
+        .unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(4).unwrap(), false, &program)
+            .with_passes(&PassManager::new(OptLevel::O0));
+        let other = tokio::spawn(async {});
+        let mut output = vec![];
+        let result = virtual_machine
+            .interpret_async(&mut tokio::io::empty(), &mut output)
+            .await;
+
+        assert!(other.is_finished());
+        match result {
+            Err(BrainfuckRuntimeError::CannotMoveLeftError(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 56, RawInstruction::MoveLeft));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+        assert_eq!(output, b"\n");
+    }
+}
@@ -33,7 +33,7 @@ impl<W: Write> AutoNewlineWriter<W> {
 
 impl<W: Write> Write for AutoNewlineWriter<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.last_written_char_is_newline = buf.last().map_or(false, |&char| char == b'\n');
+        self.last_written_char_is_newline = buf.last().is_some_and(|&char| char == b'\n');
         self.writer.write(buf)
     }
 
@@ -45,7 +45,7 @@ impl<W: Write> Write for AutoNewlineWriter<W> {
 impl<W: Write> Drop for AutoNewlineWriter<W> {
     fn drop(&mut self) {
         if !self.last_written_char_is_newline {
-            let _ = self.write(&[b'\n']);
+            let _ = self.write(b"\n");
         }
         let _ = self.flush();
     }
@@ -61,7 +61,7 @@ mod tests {
     fn should_add_a_newline() {
         let mut writer: Cursor<Vec<u8>> = Cursor::new(vec![]);
         let mut auto_newline_writer = AutoNewlineWriter::new(&mut writer);
-        let buf = &[b'a'];
+        let buf = b"a";
         let _ = auto_newline_writer.write(buf);
 
         drop(auto_newline_writer);
@@ -73,7 +73,7 @@ mod tests {
     fn should_not_add_newline_after_a_newline() {
         let mut writer: Cursor<Vec<u8>> = Cursor::new(vec![]);
         let mut auto_newline_writer = AutoNewlineWriter::new(&mut writer);
-        let buf = &[b'\n'];
+        let buf = b"\n";
         let _ = auto_newline_writer.write(buf);
 
         drop(auto_newline_writer);
@@ -1,5 +1,8 @@
 //! Brainfuck specific errors we might encounter at run time.
-use bf_types::Instruction;
+use crate::state_context::StateContext;
+use bf_types::diagnostic::Diagnostic;
+use bf_types::snippet::Snippet;
+use bf_types::{Instruction, Program, RawInstruction};
 use std::error::Error;
 use std::fmt;
 use std::path::PathBuf;
@@ -17,6 +20,147 @@ pub enum BrainfuckRuntimeError {
 
     /// IO error for failed to output Brainfuck result as [u8].
     CannotWriteOutputError(std::io::Error, PathBuf, Instruction),
+
+    /// A cell would go beyond its max value or below its min value, and the [VM](crate::VM) is not allowed to wrap or
+    /// saturate it. Also holds the index of the cell.
+    CellOverflowError(PathBuf, Instruction, isize),
+
+    /// The [VM](crate::VM) memory would need more than its memory limit or its max number of cells, or more than could
+    /// be allocated.
+    MemoryLimitExceeded(PathBuf, Instruction),
+
+    /// The [VM](crate::VM) already ran as many steps as it is allowed to before this instruction. Also holds the number
+    /// of steps it ran.
+    StepLimitExceeded(PathBuf, Instruction, usize),
+
+    /// The [CancellationToken](crate::cancellation::CancellationToken) of the [VM](crate::VM) was cancelled before this
+    /// instruction.
+    Cancelled(PathBuf, Instruction),
+
+    /// Any other error, along with the state the [VM](crate::VM) was in when running into it. Only given by a
+    /// [VM](crate::VM) asked for it, see [VM::with_state_context](crate::VM::with_state_context), in which case every
+    /// error it gives is wrapped in this one, so match on [BrainfuckRuntimeError::inner] to tell them apart.
+    WithState(Box<BrainfuckRuntimeError>, Box<StateContext>),
+}
+
+impl BrainfuckRuntimeError {
+    /// The error itself, without the state the [VM](crate::VM) was in, see [BrainfuckRuntimeError::WithState].
+    pub fn inner(&self) -> &BrainfuckRuntimeError {
+        match self {
+            BrainfuckRuntimeError::WithState(error, _) => error.inner(),
+            error => error,
+        }
+    }
+
+    /// The state the [VM](crate::VM) was in when running into this error, if it was asked for it.
+    pub fn state(&self) -> Option<&StateContext> {
+        match self {
+            BrainfuckRuntimeError::WithState(_, state) => Some(state),
+            _ => None,
+        }
+    }
+
+    /// The instruction which failed, or was about to run when the [VM](crate::VM) stopped.
+    pub fn instruction(&self) -> Instruction {
+        match self {
+            BrainfuckRuntimeError::CannotMoveLeftError(_, ins)
+            | BrainfuckRuntimeError::CannotMoveRightError(_, ins)
+            | BrainfuckRuntimeError::CannotReadInputError(_, _, ins)
+            | BrainfuckRuntimeError::CannotWriteOutputError(_, _, ins)
+            | BrainfuckRuntimeError::CellOverflowError(_, ins, _)
+            | BrainfuckRuntimeError::MemoryLimitExceeded(_, ins)
+            | BrainfuckRuntimeError::StepLimitExceeded(_, ins, _)
+            | BrainfuckRuntimeError::Cancelled(_, ins) => *ins,
+            BrainfuckRuntimeError::WithState(error, _) => error.instruction(),
+        }
+    }
+
+    /// Stable code of this error, see [diagnostic](bf_types::diagnostic).
+    pub fn code(&self) -> &'static str {
+        match self {
+            BrainfuckRuntimeError::CannotMoveLeftError(..) => "E0101",
+            BrainfuckRuntimeError::CannotMoveRightError(..) => "E0102",
+            BrainfuckRuntimeError::CannotReadInputError(..) => "E0103",
+            BrainfuckRuntimeError::CannotWriteOutputError(..) => "E0104",
+            BrainfuckRuntimeError::CellOverflowError(..) => "E0105",
+            BrainfuckRuntimeError::MemoryLimitExceeded(..) => "E0106",
+            BrainfuckRuntimeError::StepLimitExceeded(..) => "E0107",
+            BrainfuckRuntimeError::Cancelled(..) => "E0108",
+            BrainfuckRuntimeError::WithState(error, _) => error.code(),
+        }
+    }
+
+    /// What went wrong, without where it did unlike its [fmt::Display], for when the location is shown on its own.
+    pub fn message(&self) -> String {
+        match self {
+            BrainfuckRuntimeError::CannotMoveLeftError(..) => {
+                "pointer already at the left edge of the memory".to_owned()
+            }
+            BrainfuckRuntimeError::CannotMoveRightError(..) => {
+                "pointer already at the right edge of the memory, which cannot extend".to_owned()
+            }
+            BrainfuckRuntimeError::CannotReadInputError(io_error, ..) => {
+                format!("failed to read input: {}", io_error)
+            }
+            BrainfuckRuntimeError::CannotWriteOutputError(io_error, ..) => {
+                format!("failed to write output: {}", io_error)
+            }
+            BrainfuckRuntimeError::CellOverflowError(_, ins, cell) => format!(
+                "cell {} is already at its {} value",
+                cell,
+                if ins.raw_instruction() == RawInstruction::Decrement {
+                    "min"
+                } else {
+                    "max"
+                }
+            ),
+            BrainfuckRuntimeError::MemoryLimitExceeded(..) => {
+                "memory would grow beyond what the VM is allowed to use".to_owned()
+            }
+            BrainfuckRuntimeError::StepLimitExceeded(.., steps) => {
+                format!("reached the step limit of {}", steps)
+            }
+            BrainfuckRuntimeError::Cancelled(..) => "interpreting was cancelled".to_owned(),
+            BrainfuckRuntimeError::WithState(error, _) => error.message(),
+        }
+    }
+
+    /// Describes this error as a [Diagnostic]. `program` has to be the [Program] the [VM](crate::VM) was interpreting,
+    /// see [VM::diagnostic](crate::VM::diagnostic) for one which also tells the state of the [VM](crate::VM).
+    pub fn diagnostic(&self, program: &Program) -> Diagnostic {
+        Diagnostic::new(self.code(), self.message(), program.file_path())
+            .with_location(program, self.instruction())
+    }
+
+    /// Renders this error like rustc does, with the line of `program` it happened on, and the state the
+    /// [VM](crate::VM) was in beneath it if any. `program` has to be the [Program] the [VM](crate::VM) was interpreting.
+    pub fn snippet<'a>(&self, program: &'a Program) -> Snippet<'a> {
+        let snippet =
+            Snippet::new(program, self.message()).with_label(self.instruction(), self.label());
+        match self.state() {
+            Some(state) => snippet
+                .with_note(state.to_string())
+                .with_note(state.tape_window()),
+            None => snippet,
+        }
+    }
+
+    /// What the failed instruction could not do, shown next to it in a [Snippet].
+    fn label(&self) -> String {
+        match self {
+            BrainfuckRuntimeError::CannotMoveLeftError(..) => "cannot move left".to_owned(),
+            BrainfuckRuntimeError::CannotMoveRightError(..) => "cannot move right".to_owned(),
+            BrainfuckRuntimeError::CannotReadInputError(..) => "cannot read input".to_owned(),
+            BrainfuckRuntimeError::CannotWriteOutputError(..) => "cannot write output".to_owned(),
+            BrainfuckRuntimeError::CellOverflowError(_, _, cell) => {
+                format!("overflows cell {}", cell)
+            }
+            BrainfuckRuntimeError::MemoryLimitExceeded(..) => "out of memory".to_owned(),
+            BrainfuckRuntimeError::StepLimitExceeded(..) => "out of steps".to_owned(),
+            BrainfuckRuntimeError::Cancelled(..) => "cancelled".to_owned(),
+            BrainfuckRuntimeError::WithState(error, _) => error.label(),
+        }
+    }
 }
 
 impl fmt::Display for BrainfuckRuntimeError {
@@ -45,8 +189,77 @@ impl fmt::Display for BrainfuckRuntimeError {
                     "[{}:{}:{}] wants to write a value but failed due to {}",
                     file_path.display(), ins.row(), ins.col(), io_error
                 ),
+            BrainfuckRuntimeError::CellOverflowError(file_path, ins, cell) =>
+                write!(
+                    f,
+                    "[{}:{}:{}] wants to {} cell {} but it is already at its {} value",
+                    file_path.display(), ins.row(), ins.col(),
+                    if ins.raw_instruction() == RawInstruction::Decrement { "decrement" } else { "increment" },
+                    cell,
+                    if ins.raw_instruction() == RawInstruction::Decrement { "min" } else { "max" },
+                ),
+            BrainfuckRuntimeError::MemoryLimitExceeded(file_path, ins) =>
+                write!(
+                    f,
+                    "[{}:{}:{}] needs more memory than the VM is allowed to use",
+                    file_path.display(), ins.row(), ins.col()
+                ),
+            BrainfuckRuntimeError::StepLimitExceeded(file_path, ins, steps) =>
+                write!(
+                    f,
+                    "VM already ran its {} allowed steps before reaching [{}:{}:{}]",
+                    steps, file_path.display(), ins.row(), ins.col()
+                ),
+            BrainfuckRuntimeError::Cancelled(file_path, ins) =>
+                write!(
+                    f,
+                    "VM was cancelled before reaching [{}:{}:{}]",
+                    file_path.display(), ins.row(), ins.col()
+                ),
+            BrainfuckRuntimeError::WithState(error, _) => write!(f, "{}", error),
         }
     }
 }
 
 impl Error for BrainfuckRuntimeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::VM;
+    use std::io::{stdin, stdout};
+    use std::num::NonZeroUsize;
+
+    /// Should render the line of the failed instruction with a caret under it.
+    #[test]
+    fn render_snippet() {
+        let program = Program::new("f.bf", "+\n>> move\n").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+        let error = virtual_machine
+            .interpret(&mut stdin(), &mut stdout())
+            .unwrap_err();
+
+        assert_eq!(
+            error.snippet(&program).to_string(),
+            "error: pointer already at the right edge of the memory, which cannot extend\n --> f.bf:2:2\n  |\n2 | >> move\n  |  ^ cannot move right"
+        );
+    }
+
+    /// Should render the state the VM was in beneath the source code.
+    #[test]
+    fn render_snippet_with_state() {
+        let program = Program::new("f.bf", "+<").validated().unwrap();
+        let mut virtual_machine: VM<u8> =
+            VM::new(NonZeroUsize::new(3).unwrap(), false, &program).with_state_context(Some(1));
+        let error = virtual_machine
+            .interpret(&mut stdin(), &mut stdout())
+            .unwrap_err();
+
+        assert_eq!(
+            error.snippet(&program).to_string(),
+            "error: pointer already at the left edge of the memory\n --> f.bf:1:2\n  |\n\
+             1 | +<\n  |  ^ cannot move left\n  |\n  = note: pointer at 0, at op 1 after 2 steps\n  \
+             = note: cells 0..=1: [1] 0"
+        );
+    }
+}
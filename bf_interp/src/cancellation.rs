@@ -0,0 +1,34 @@
+//! Stopping a running [VM](crate::VM) from another thread.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A handle which cancels every [VM](crate::VM) it was given to, see [VM::with_cancellation](crate::VM::with_cancellation).
+/// Clones share the same state, so one can be kept to cancel the [VM](crate::VM) while another is given to it.
+/// # Example
+/// ```
+/// # use bf_interp::cancellation::CancellationToken;
+/// let token = CancellationToken::new();
+/// let handle = token.clone();
+/// std::thread::spawn(move || handle.cancel()).join().unwrap();
+/// assert!(token.is_cancelled());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    /// Creates a token which is not cancelled yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Asks every [VM](crate::VM) holding this token to stop. It cannot be undone.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// If [CancellationToken::cancel] was called on this token or any of its clones.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -1,4 +1,11 @@
 //! Common behavior for a Brainfuck [crate::VM] cell.
+//!
+//! [CellKind] is implemented for every unsigned and signed integer from 8 to 64 bits. Brainfuck I/O is byte oriented,
+//! so cells wider than a byte map to bytes by one rule, regardless of their width and signedness:
+//! - `,` stores the byte read as its value from 0 to 255, except for `i8` cells which store the byte as is, i.e., bytes
+//!   from 128 to 255 become negative values from -128 to -1.
+//! - `.` writes the lowest byte of the cell in two's complement, i.e., the cell value modulo 256. So a 16-bit cell
+//!   holding 321 writes `A`, and a signed cell holding -1 writes byte 255.
 
 use std::cmp::PartialOrd;
 use std::ops::{AddAssign, SubAssign};
@@ -18,12 +25,15 @@ pub trait CellKind: AddAssign + SubAssign + PartialOrd + Sized + Clone {
     /// Min value which T can represent.
     fn min() -> Self;
 
-    /// Set a given u8 value to a T type variable.
+    /// Set a given u8 value to a T type variable, as `,` does. See the [module documentation](self) for the rule.
     fn set_value(&mut self, value: u8);
 
-    /// Get the underlying data as u8.
+    /// Get the underlying data as u8, as `.` does. See the [module documentation](self) for the rule.
     fn get_value(&self) -> u8;
 
+    /// The value of the cell as an i128, which holds every value of every implementing type.
+    fn to_i128(&self) -> i128;
+
     /// Increment a T type value by one. The result is wrapped to be less than or equal to T type max value.
     fn increment(&mut self) {
         if self < &mut CellKind::max() {
@@ -41,30 +51,173 @@ pub trait CellKind: AddAssign + SubAssign + PartialOrd + Sized + Clone {
             *self = CellKind::max();
         }
     }
-}
 
-impl CellKind for u8 {
-    fn zero() -> Self {
-        0_u8
+    /// Increment a T type value by `amount`, a negative `amount` decrements it. The result is wrapped exactly as
+    /// calling [CellKind::increment] or [CellKind::decrement] that many times would do.
+    fn increment_by(&mut self, amount: isize) {
+        if amount >= 0 {
+            (0..amount).for_each(|_| self.increment());
+        } else {
+            (amount..0).for_each(|_| self.decrement());
+        }
     }
 
-    fn one() -> Self {
-        1_u8
+    /// Increment a T type value by `amount`, a negative `amount` decrements it. Unlike [CellKind::increment_by], the
+    /// result stops at T type max or min value. Returns how many of the `amount.abs()` single steps were taken before
+    /// reaching it.
+    fn saturating_increment_by(&mut self, amount: isize) -> usize {
+        let bound = if amount >= 0 {
+            CellKind::max()
+        } else {
+            CellKind::min()
+        };
+        let mut steps = 0;
+        while steps < amount.unsigned_abs() && *self != bound {
+            self.increment_by(amount.signum());
+            steps += 1;
+        }
+        steps
     }
 
-    fn max() -> Self {
-        255_u8
+    /// Increment a T type value by `value` multiplied by `factor`. The result is wrapped exactly as a loop which
+    /// decrements `value` down to zero and increments this value by `factor` each time would do.
+    fn multiply_add(&mut self, value: &Self, factor: isize) {
+        let mut counter = value.clone();
+        while counter != Self::zero() {
+            counter.decrement();
+            self.increment_by(factor);
+        }
     }
+}
+
+/// Implements [CellKind] for primitive integers, which are all two's complement, so wrapping arithmetic on them gives
+/// exactly what [CellKind::increment] and [CellKind::decrement] would.
+macro_rules! impl_cell_kind {
+    ($($t:ty),*) => {
+        $(
+            impl CellKind for $t {
+                fn zero() -> Self {
+                    0
+                }
+
+                fn one() -> Self {
+                    1
+                }
+
+                fn max() -> Self {
+                    <$t>::MAX
+                }
+
+                fn min() -> Self {
+                    <$t>::MIN
+                }
+
+                fn set_value(&mut self, value: u8) {
+                    // Zero-extends the byte, except that i8 reinterprets it.
+                    *self = value as $t;
+                }
+
+                fn get_value(&self) -> u8 {
+                    // Keeps the lowest byte.
+                    *self as u8
+                }
+
+                fn to_i128(&self) -> i128 {
+                    *self as i128
+                }
+
+                fn increment_by(&mut self, amount: isize) {
+                    *self = self.wrapping_add(amount as $t);
+                }
 
-    fn min() -> Self {
-        0_u8
+                fn saturating_increment_by(&mut self, amount: isize) -> usize {
+                    // i128 holds every value of every implementing type, as well as their sum with any isize.
+                    let before = *self as i128;
+                    let after = (before + amount as i128).clamp(<$t>::MIN as i128, <$t>::MAX as i128);
+                    *self = after as $t;
+                    (after - before).unsigned_abs() as usize
+                }
+
+                fn multiply_add(&mut self, value: &Self, factor: isize) {
+                    *self = self.wrapping_add(value.wrapping_mul(factor as $t));
+                }
+            }
+        )*
+    };
+}
+
+impl_cell_kind!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Should store a byte read as its value from 0 to 255, except for i8.
+    #[test]
+    fn set_value() {
+        let mut cell: u16 = 1000;
+        cell.set_value(200);
+        assert_eq!(cell, 200);
+
+        let mut cell: i32 = -1000;
+        cell.set_value(200);
+        assert_eq!(cell, 200);
+
+        let mut cell: i8 = 0;
+        cell.set_value(200);
+        assert_eq!(cell, -56);
     }
 
-    fn set_value(&mut self, value: u8) {
-        *self = value;
+    /// Should write the lowest byte of a cell.
+    #[test]
+    fn get_value() {
+        assert_eq!(321_u16.get_value(), b'A');
+        assert_eq!((-1_i64).get_value(), 255);
+        assert_eq!((-56_i8).get_value(), 200);
+        assert_eq!(u32::MAX.get_value(), 255);
     }
 
-    fn get_value(&self) -> u8 {
-        *self
+    /// Should give the value of a cell as is, however wide it is.
+    #[test]
+    fn to_i128() {
+        assert_eq!(200_u8.to_i128(), 200);
+        assert_eq!((-56_i8).to_i128(), -56);
+        assert_eq!(u64::MAX.to_i128(), u64::MAX as i128);
+        assert_eq!(i64::MIN.to_i128(), i64::MIN as i128);
+    }
+
+    /// Should wrap wide cells the same way as incrementing or decrementing one at a time does.
+    #[test]
+    fn wrap_wide_cells() {
+        let mut cell = u16::MAX;
+        cell.increment_by(2);
+        assert_eq!(cell, 1);
+
+        let mut cell = i16::MIN;
+        cell.increment();
+        cell.increment_by(-2);
+        assert_eq!(cell, i16::MAX);
+
+        let mut cell: i8 = 10;
+        let mut expected: i8 = 10;
+        cell.multiply_add(&-3, 7);
+        (0..253).for_each(|_| expected.increment_by(7));
+        assert_eq!(cell, expected);
+    }
+
+    /// Should stop at the bounds, and tell how many steps were taken before reaching them.
+    #[test]
+    fn saturate() {
+        let mut cell: u8 = 250;
+        assert_eq!(cell.saturating_increment_by(10), 5);
+        assert_eq!(cell, 255);
+
+        let mut cell = i64::MIN + 1;
+        assert_eq!(cell.saturating_increment_by(-3), 1);
+        assert_eq!(cell, i64::MIN);
+
+        let mut cell = u64::MAX;
+        assert_eq!(cell.saturating_increment_by(-3), 3);
+        assert_eq!(cell, u64::MAX - 3);
     }
 }
@@ -0,0 +1,34 @@
+//! What the [VM](crate::VM) does when a Brainfuck program reads input after it has run out.
+
+use bf_types::impl_named;
+
+/// Brainfuck leaves the end of input undefined, so programs rely on different conventions. This policy picks one of
+/// them.
+/// # Example
+/// ```
+/// # use bf_interp::eof_policy::EofPolicy;
+/// assert_eq!("minus-one".parse::<EofPolicy>(), Ok(EofPolicy::MinusOne));
+/// assert!("ignore".parse::<EofPolicy>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EofPolicy {
+    /// Fail with a [CannotReadInputError](crate::brainfuck_runtime_error::BrainfuckRuntimeError::CannotReadInputError).
+    #[default]
+    Error,
+
+    /// Leave the current cell unchanged.
+    Unchanged,
+
+    /// Set the current cell to zero.
+    Zero,
+
+    /// Set the current cell to -1, which is the max value for unsigned cells.
+    MinusOne,
+}
+
+impl_named!(EofPolicy, "EOF policy", {
+    Error => "error",
+    Unchanged => "unchanged",
+    Zero => "zero",
+    MinusOne => "minus-one",
+});
@@ -0,0 +1,60 @@
+//! When the [VM](crate::VM) flushes what a Brainfuck program has written so far.
+
+use bf_types::impl_named;
+
+/// The [VM](crate::VM) buffers its output and flushes it according to this policy. No matter the policy, everything is
+/// flushed when [VM::interpret](crate::VM::interpret) or its async counterpart returns. [VM::step](crate::VM::step) and
+/// [VM::run_until](crate::VM::run_until) only flush when the policy asks for it, so output of a program run that way
+/// may be left buffered in the write destination when it halts, while [VM::resume](crate::VM::resume) keeps output
+/// until taken, whatever the policy.
+///
+/// The default flushes after every byte so that nothing is left behind when stepping, whereas the command line
+/// defaults to [FlushPolicy::Newline] as it only runs whole programs, which are flushed once they exit anyway.
+/// # Example
+/// ```
+/// # use bf_interp::flush_policy::FlushPolicy;
+/// assert_eq!("before-input".parse::<FlushPolicy>(), Ok(FlushPolicy::BeforeInput));
+/// assert!("never".parse::<FlushPolicy>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// Flush after every single byte, the slowest one.
+    #[default]
+    Always,
+
+    /// Flush after every newline character, and before reading any input so that prompts are shown.
+    Newline,
+
+    /// Flush only before reading any input, so that prompts are shown.
+    BeforeInput,
+
+    /// Flush only when the program exits, the fastest one, but prompts may not be shown before reading input. A
+    /// program which is stepped through is never flushed.
+    AtExit,
+}
+
+impl_named!(FlushPolicy, "flush policy", {
+    Always => "always",
+    Newline => "newline",
+    BeforeInput => "before-input",
+    AtExit => "at-exit",
+});
+
+impl FlushPolicy {
+    /// If output should be flushed right after writing `value`.
+    pub fn flush_after(&self, value: u8) -> bool {
+        match self {
+            FlushPolicy::Always => true,
+            FlushPolicy::Newline => value == b'\n',
+            FlushPolicy::BeforeInput | FlushPolicy::AtExit => false,
+        }
+    }
+
+    /// If output should be flushed before reading input.
+    pub fn flush_before_input(&self) -> bool {
+        match self {
+            FlushPolicy::Always | FlushPolicy::AtExit => false,
+            FlushPolicy::Newline | FlushPolicy::BeforeInput => true,
+        }
+    }
+}
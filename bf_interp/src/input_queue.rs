@@ -0,0 +1,66 @@
+//! Input which is handed to a [VM](crate::VM) bit by bit, instead of being read from a blocking source.
+
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read};
+
+/// Bytes waiting to be read by a [VM](crate::VM). Reading from an empty queue fails with [ErrorKind::WouldBlock], so the
+/// [VM](crate::VM) waits for more with a [StepStatus::NeedsInput](crate::step_status::StepStatus::NeedsInput), until the
+/// queue is closed and reading from it gives an end of file instead.
+/// # Example
+/// ```
+/// # use bf_interp::input_queue::InputQueue;
+/// use std::io::{ErrorKind, Read};
+/// let mut queue = InputQueue::new();
+/// let mut buf = [0; 1];
+/// assert_eq!(queue.read(&mut buf).unwrap_err().kind(), ErrorKind::WouldBlock);
+/// queue.push(b"a");
+/// queue.close();
+/// assert_eq!(queue.read(&mut buf).unwrap(), 1);
+/// assert_eq!(queue.read(&mut buf).unwrap(), 0);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct InputQueue {
+    bytes: VecDeque<u8>,
+    closed: bool,
+}
+
+impl InputQueue {
+    /// Creates an empty queue which is still open.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `bytes` at the end of the queue.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.bytes.extend(bytes);
+    }
+
+    /// Tells that nothing more will be pushed, so reading beyond what is left is an end of file.
+    pub fn close(&mut self) {
+        self.closed = true;
+    }
+
+    /// If [InputQueue::close] was called.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Number of bytes not read yet.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// If every byte pushed so far has been read.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+}
+
+impl Read for InputQueue {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.bytes.is_empty() && !self.closed && !buf.is_empty() {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        self.bytes.read(buf)
+    }
+}
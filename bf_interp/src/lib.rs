@@ -1,16 +1,40 @@
 //! A representation of Brainfuck virtual machine and the actual interpret functions.
 
+#[cfg(feature = "async")]
+mod async_interp;
 pub mod auto_newline_writer;
 pub mod brainfuck_runtime_error;
+pub mod cancellation;
 pub mod cell_kind;
+pub mod eof_policy;
+pub mod flush_policy;
+pub mod input_queue;
+pub mod overflow_policy;
+pub mod program_ref;
+pub mod state_context;
+pub mod step_status;
+pub mod tape;
 
 use auto_newline_writer::AutoNewlineWriter;
-use bf_types::{Program, RawInstruction};
+use bf_types::diagnostic::Diagnostic;
+use bf_types::ir::{Ir, Op, Span, SpannedOp};
+use bf_types::pass_manager::{Pass, PassManager};
+use bf_types::{Instruction, RawInstruction, ValidatedProgram};
 use brainfuck_runtime_error::BrainfuckRuntimeError;
+use cancellation::CancellationToken;
 use cell_kind::CellKind;
-use std::collections::HashMap;
-use std::io::{Read, Write};
+use eof_policy::EofPolicy;
+use flush_policy::FlushPolicy;
+use input_queue::InputQueue;
+use overflow_policy::OverflowPolicy;
+use program_ref::ProgramRef;
+use state_context::StateContext;
+use std::cell::OnceCell;
+use std::io::{BufWriter, ErrorKind, Read, Write};
 use std::num::NonZeroUsize;
+use std::sync::Arc;
+use step_status::StepStatus;
+use tape::{Backend, GrowthPolicy, OutOfMemory, Tape, Topology};
 
 /// The Brainfuck virtual machine. It can hold data of type T which implements [CellKind] trait.
 #[derive(Debug)]
@@ -18,27 +42,52 @@ pub struct VM<'a, T>
 where
     T: CellKind,
 {
-    memory: Vec<T>,
-    pointer: usize,
-    can_extend: bool,
+    memory: Tape<T>,
+    memory_size: NonZeroUsize,
+    pointer: isize,
+    topology: Topology,
     program_counter: usize,
-    program: &'a Program,
-    open_to_close: HashMap<usize, usize>,
-    close_to_open: HashMap<usize, usize>,
+    program: ProgramRef<'a>,
+    passes: PassManager,
+    compiled: OnceCell<Arc<Compiled>>,
+    flush_policy: FlushPolicy,
+    eof_policy: EofPolicy,
+    overflow_policy: OverflowPolicy,
+    steps: usize,
+    max_steps: Option<usize>,
+    cancellation: Option<CancellationToken>,
+    state_window: Option<usize>,
+    input: InputQueue,
+    output: Vec<u8>,
+    last_output: Option<usize>,
 }
 
+/// The [Ir] a [VM] runs along with its jump table, see [VM::compile]. Shared so that a run can hold on to it while the
+/// [VM] changes.
+#[derive(Debug)]
+struct Compiled {
+    ir: Ir,
+    jump_table: Vec<usize>,
+}
+
+/// How many steps a [VM] runs between two checks of its [CancellationToken].
+const CANCELLATION_INTERVAL: usize = 1024;
+
 impl<'a, T> VM<'a, T>
 where
     T: CellKind,
 {
-    /// Constructs a new Brainfuck [VM] and initialize it with a borrow of [Program].
+    /// Constructs a new Brainfuck [VM] and initialize it with a borrow of [ValidatedProgram].
     ///
     /// `memory_size` specifies how much cells the [VM] memory can hold.
     ///
-    /// `can_extend` specifies if the memory can extend when it's full.
+    /// `can_extend` specifies if the memory can extend to the right when it's full, see [VM::with_topology] for other
+    /// choices.
     ///
-    /// `program` is a borrow to a [Program] struct which this [VM] will later interpret.
-    /// It is assumed that `program` is a valid one, i.e., it can pass `program.validate();`
+    /// `program` is a borrow to a [ValidatedProgram] which this [VM] will later interpret, so its brackets are known to
+    /// match, see [Program::validated](bf_types::Program::validated).
+    /// The [VM] actually interprets the [Ir] of `program`, optimized by the default [PassManager]. See
+    /// [VM::with_passes] for choosing other passes.
     /// # Example
     /// ```no_run
     /// # use bf_interp::*;
@@ -46,46 +95,370 @@ where
     /// use bf_types::Program;
     /// # use std::io;
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
-    /// let program = Program::from_file("hello_world.bf")?;
+    /// let program = Program::from_file("hello_world.bf")?.validated()?;
     /// let virtual_machine:VM<u8> = VM::new(NonZeroUsize::new(100).unwrap(), true, &program);
     /// # Ok(())
     /// # }
     /// ```
-    pub fn new(memory_size: NonZeroUsize, can_extend: bool, program: &'a Program) -> VM<'a, T> {
-        let mut memory: Vec<T> = vec![];
-        memory.resize(memory_size.get(), T::zero());
-
-        // Construct matching brackets.
-        let mut open_to_close: HashMap<usize, usize> = HashMap::new();
-        let mut stack: Vec<usize> = Vec::with_capacity(program.instructions().len());
-        for (idx, ins) in program.instructions().iter().enumerate() {
-            if ins.raw_instruction() == RawInstruction::BeginLoop {
-                stack.push(idx);
-            } else if ins.raw_instruction() == RawInstruction::EndLoop {
-                let open_idx = stack.pop().unwrap();
-                open_to_close.insert(open_idx, idx);
-            }
-        }
+    pub fn new(
+        memory_size: NonZeroUsize,
+        can_extend: bool,
+        program: &'a ValidatedProgram,
+    ) -> VM<'a, T> {
+        Self::with_program(memory_size, can_extend, ProgramRef::Borrowed(program))
+    }
 
-        // Construct the reverse HashMap.
-        let mut close_to_open: HashMap<usize, usize> = HashMap::new();
-        for (open_idx, close_idx) in open_to_close.iter() {
-            close_to_open.insert(*close_idx, *open_idx);
-        }
+    /// Constructs a new Brainfuck [VM] like [VM::new], but holding `program` itself, either shared with others through
+    /// an [Arc] or owned by the [VM]. Such a [VM] borrows nothing, so it can be stored anywhere or sent to another
+    /// thread.
+    /// # Example
+    /// ```no_run
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use std::sync::Arc;
+    /// use bf_types::Program;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Arc::new(Program::from_file("hello_world.bf")?.validated()?);
+    /// let mut virtual_machine: VM<'static, u8> =
+    ///     VM::with_shared_program(NonZeroUsize::new(100).unwrap(), true, program.clone());
+    /// let handle = std::thread::spawn(move || {
+    ///     virtual_machine.interpret(&mut std::io::stdin(), &mut std::io::stdout())
+    /// });
+    /// handle.join().unwrap()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_shared_program(
+        memory_size: NonZeroUsize,
+        can_extend: bool,
+        program: impl Into<Arc<ValidatedProgram>>,
+    ) -> VM<'static, T> {
+        VM::with_program(memory_size, can_extend, ProgramRef::Shared(program.into()))
+    }
+
+    /// Constructs a new Brainfuck [VM] holding `program` however it is given, see [VM::new].
+    fn with_program(
+        memory_size: NonZeroUsize,
+        can_extend: bool,
+        program: ProgramRef<'a>,
+    ) -> VM<'a, T> {
+        let memory = Tape::new(memory_size);
+        let topology = if can_extend {
+            Topology::Extensible
+        } else {
+            Topology::Bounded
+        };
 
         // Construct the VM.
         Self {
             memory,
+            memory_size,
             pointer: 0,
-            can_extend,
+            topology,
             program_counter: 0,
             program,
-            open_to_close,
-            close_to_open,
+            passes: PassManager::default(),
+            compiled: OnceCell::new(),
+            flush_policy: FlushPolicy::default(),
+            eof_policy: EofPolicy::default(),
+            overflow_policy: OverflowPolicy::default(),
+            steps: 0,
+            max_steps: None,
+            cancellation: None,
+            state_window: None,
+            input: InputQueue::new(),
+            output: Vec::new(),
+            last_output: None,
+        }
+    }
+
+    /// Lowers the borrowed [ValidatedProgram] with the passes of `passes` instead of the default ones. Passes which only
+    /// hold for wrapping cells are skipped unless the overflow policy is [OverflowPolicy::Wrapping]. Like every other
+    /// setting, it only takes effect once the [Ir] is needed, so the program is lowered only once however many settings
+    /// change it.
+    /// # Example
+    /// ```no_run
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_types::pass_manager::{OptLevel, PassManager};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Program::from_file("hello_world.bf")?.validated()?;
+    /// let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(100).unwrap(), true, &program)
+    ///     .with_passes(&PassManager::new(OptLevel::O0));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_passes(mut self, passes: &PassManager) -> Self {
+        self.passes = passes.clone();
+        self.compiled = OnceCell::new();
+        self
+    }
+
+    /// Sets when the output is flushed, it is flushed after every single byte by default, see [FlushPolicy] for why.
+    /// # Example
+    /// ```no_run
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_interp::flush_policy::FlushPolicy;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Program::from_file("hello_world.bf")?.validated()?;
+    /// let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(100).unwrap(), true, &program)
+    ///     .with_flush_policy(FlushPolicy::BeforeInput);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_flush_policy(mut self, flush_policy: FlushPolicy) -> Self {
+        self.flush_policy = flush_policy;
+        self
+    }
+
+    /// Sets what happens when the program reads input after it has run out, it fails with an error by default.
+    /// # Example
+    /// ```no_run
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_interp::eof_policy::EofPolicy;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Program::from_file("hello_world.bf")?.validated()?;
+    /// let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(100).unwrap(), true, &program)
+    ///     .with_eof_policy(EofPolicy::Zero);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_eof_policy(mut self, eof_policy: EofPolicy) -> Self {
+        self.eof_policy = eof_policy;
+        self
+    }
+
+    /// Sets what happens when a cell goes beyond its bounds, it wraps around by default. Should be called before
+    /// interpreting anything.
+    /// # Example
+    /// ```no_run
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_interp::overflow_policy::OverflowPolicy;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Program::from_file("hello_world.bf")?.validated()?;
+    /// let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(100).unwrap(), true, &program)
+    ///     .with_overflow_policy(OverflowPolicy::Error);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_overflow_policy(mut self, overflow_policy: OverflowPolicy) -> Self {
+        self.overflow_policy = overflow_policy;
+        self.compiled = OnceCell::new();
+        self
+    }
+
+    /// Sets what happens when the pointer moves beyond an edge of the memory, overriding `can_extend` of [VM::new].
+    /// # Example
+    /// ```no_run
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_interp::tape::Topology;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Program::from_file("hello_world.bf")?.validated()?;
+    /// let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(100).unwrap(), false, &program)
+    ///     .with_topology(Topology::Bidirectional);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self.compiled = OnceCell::new();
+        self
+    }
+
+    /// Sets how the memory stores its cells, every cell is allocated by default. Should be called before interpreting
+    /// anything.
+    /// # Example
+    /// ```no_run
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_interp::tape::{Backend, Topology};
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Program::from_file("hello_world.bf")?.validated()?;
+    /// let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(100).unwrap(), true, &program)
+    ///     .with_backend(Backend::Sparse)
+    ///     .with_memory_limit(Some(1 << 20));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        let len = NonZeroUsize::new(self.memory.len()).expect("memory has at least one cell");
+        self.memory = Tape::with_backend(len, backend)
+            .with_memory_limit(self.memory.memory_limit())
+            .with_max_cells(self.memory.max_cells())
+            .with_growth_policy(self.memory.growth_policy());
+        self
+    }
+
+    /// Limits how many bytes the memory cells may take up, see [Tape::with_memory_limit]. Going beyond it fails with
+    /// a [BrainfuckRuntimeError::MemoryLimitExceeded] instead of allocating more. There is no limit by default.
+    pub fn with_memory_limit(mut self, memory_limit: Option<usize>) -> Self {
+        self.memory = self.memory.with_memory_limit(memory_limit);
+        self
+    }
+
+    /// Limits how many cells the memory may have, see [Tape::with_max_cells]. Going beyond it fails with a
+    /// [BrainfuckRuntimeError::MemoryLimitExceeded] instead of growing the memory. There is no limit by default.
+    pub fn with_max_cells(mut self, max_cells: Option<usize>) -> Self {
+        self.memory = self.memory.with_max_cells(max_cells);
+        self
+    }
+
+    /// Sets how much the memory grows once the pointer moves beyond one of its edges, it doubles by default.
+    /// # Example
+    /// ```no_run
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_interp::tape::GrowthPolicy;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Program::from_file("hello_world.bf")?.validated()?;
+    /// let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(100).unwrap(), true, &program)
+    ///     .with_growth_policy(GrowthPolicy::Increment(NonZeroUsize::new(1024).unwrap()))
+    ///     .with_max_cells(Some(1 << 16));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_growth_policy(mut self, growth_policy: GrowthPolicy) -> Self {
+        self.memory = self.memory.with_growth_policy(growth_policy);
+        self
+    }
+
+    /// Limits how many steps the [VM] may run, where a step is one [Op] of its [Ir] or one move of an [Op::Scan], so
+    /// that a program which would run forever fails with a [BrainfuckRuntimeError::StepLimitExceeded] instead. The more
+    /// optimized the [Ir], the fewer steps a program needs. There is no limit by default.
+    /// # Example
+    /// ```no_run
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Program::from_file("hello_world.bf")?.validated()?;
+    /// let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(100).unwrap(), true, &program)
+    ///     .with_max_steps(Some(1_000_000));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_max_steps(mut self, max_steps: Option<usize>) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Lets another thread stop the [VM] by cancelling `cancellation`, it then fails with a
+    /// [BrainfuckRuntimeError::Cancelled] within a few steps. The [VM] is left as it was before the step it did not
+    /// run, so its memory and pointer can still be inspected. A [VM] waiting for input only stops once it gets some.
+    /// # Example
+    /// ```no_run
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use std::time::Duration;
+    /// use bf_types::Program;
+    /// use bf_interp::cancellation::CancellationToken;
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let program = Program::from_file("hello_world.bf")?.validated()?;
+    /// let token = CancellationToken::new();
+    /// let mut virtual_machine: VM<u8> =
+    ///     VM::new(NonZeroUsize::new(100).unwrap(), true, &program).with_cancellation(token.clone());
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(Duration::from_secs(1));
+    ///     token.cancel();
+    /// });
+    /// let result = virtual_machine.interpret(&mut std::io::stdin(), &mut std::io::stdout());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Makes errors carry the state the [VM] was in when running into them, including `window` cells on each side of
+    /// the pointer, see [BrainfuckRuntimeError::WithState]. Errors carry no state by default.
+    ///
+    /// An error carrying its state is wrapped in [BrainfuckRuntimeError::WithState], so it no longer matches the
+    /// variant it would be without a state. Match on [BrainfuckRuntimeError::inner] instead, which gives that variant
+    /// whether the state is carried or not.
+    /// # Example
+    /// ```
+    /// # use bf_interp::*;
+    /// use std::io::{empty, sink};
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_interp::brainfuck_runtime_error::BrainfuckRuntimeError;
+    /// let program = Program::new("", "+>++>+++>").validated().unwrap();
+    /// let mut virtual_machine: VM<u8> =
+    ///     VM::new(NonZeroUsize::new(3).unwrap(), false, &program).with_state_context(Some(1));
+    /// let error = virtual_machine.interpret(&mut empty(), &mut sink()).unwrap_err();
+    /// assert!(!matches!(error, BrainfuckRuntimeError::CannotMoveRightError(..)));
+    /// assert!(matches!(error.inner(), BrainfuckRuntimeError::CannotMoveRightError(..)));
+    /// let state = error.state().unwrap();
+    /// assert_eq!(state.tape_window(), "cells 1..=2: 2 [3]");
+    /// ```
+    pub fn with_state_context(mut self, window: Option<usize>) -> Self {
+        self.state_window = window;
+        self
+    }
+
+    /// Lower the borrowed [ValidatedProgram] into its [Ir] once every setting is known, i.e., the first time the [Ir]
+    /// is needed after any setting which changes it. [Pass::ClearLoop] and [Pass::Multiply] assume cells wrap around,
+    /// e.g., `[-]` clears a saturating cell but `[+]` would never end, so they are skipped for other overflow policies.
+    /// [Pass::Multiply] and [Pass::Offset] address cells relative to the pointer without moving it, which a circular
+    /// memory cannot do near its edges, and [Pass::Scan] could go round a circular memory without a zero cell forever
+    /// within a single step, so they are all skipped for [Topology::Circular].
+    fn compile(&self) -> Compiled {
+        let mut passes = match self.overflow_policy {
+            OverflowPolicy::Wrapping => self.passes.clone(),
+            OverflowPolicy::Saturating | OverflowPolicy::Error => self
+                .passes
+                .clone()
+                .without(Pass::ClearLoop)
+                .without(Pass::Multiply),
+        };
+        if self.topology == Topology::Circular {
+            passes = passes
+                .without(Pass::Multiply)
+                .without(Pass::Offset)
+                .without(Pass::Scan);
         }
+        let ir = passes.run(&self.program);
+        let jump_table = Self::jump_table(&ir);
+        Compiled { ir, jump_table }
     }
 
-    /// Interpret the borrowed [Program] instance. User has to specify where the input and output will be.
+    /// The [Ir] and jump table of the borrowed [ValidatedProgram], lowering it first if needed, see [VM::compile].
+    fn compiled(&self) -> &Arc<Compiled> {
+        self.compiled.get_or_init(|| Arc::new(self.compile()))
+    }
+
+    /// Construct the jump table of an [Ir], in which each bracket is mapped to the index of its matching bracket, so
+    /// that loops never have to search for it. Every other [Op] is mapped to itself, i.e., it never jumps.
+    fn jump_table(ir: &Ir) -> Vec<usize> {
+        let mut jump_table: Vec<usize> = (0..ir.ops().len()).collect();
+        let mut stack: Vec<usize> = Vec::with_capacity(ir.ops().len());
+        for (idx, spanned_op) in ir.ops().iter().enumerate() {
+            if spanned_op.op() == Op::BeginLoop {
+                stack.push(idx);
+            } else if spanned_op.op() == Op::EndLoop {
+                if let Some(open_idx) = stack.pop() {
+                    jump_table[open_idx] = idx;
+                    jump_table[idx] = open_idx;
+                }
+            }
+        }
+        jump_table
+    }
+
+    /// Interpret the borrowed [ValidatedProgram] instance. User has to specify where the input and output will be.
+    /// Reading blocks until there is some input, see [VM::step] or [VM::resume] for input sources which never block.
     /// # Example
     /// ```no_run
     /// use bf_types::*;
@@ -95,7 +468,7 @@ where
     /// # use std::io;
     /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
     ///
-    /// let program = Program::from_file("./hello_world.bf")?;
+    /// let program = Program::from_file("./hello_world.bf")?.validated()?;
     /// let memory_size = NonZeroUsize::new(30000).unwrap();
     /// let mut virtual_machine: VM<u8> = VM::new(memory_size, true, &program);
     /// virtual_machine.interpret(&mut stdin(), &mut stdout())?;
@@ -107,284 +480,1560 @@ where
         read_source: &mut R,
         write_destination: &mut W,
     ) -> Result<(), BrainfuckRuntimeError> {
-        let mut auto_newline_writer = AutoNewlineWriter::new(write_destination);
-        while self.program_counter < self.program.instructions().len() {
-            let instruction = self.program.instructions()[self.program_counter];
-            self.program_counter = match instruction.raw_instruction() {
-                RawInstruction::MoveLeft => self.move_left(),
-                RawInstruction::MoveRight => self.move_right(),
-                RawInstruction::Increment => self.increment(),
-                RawInstruction::Decrement => self.decrement(),
-                RawInstruction::Input => self.read_value(read_source),
-                RawInstruction::Output => self.write_value(&mut auto_newline_writer),
-                RawInstruction::BeginLoop => self.begin_loop(),
-                RawInstruction::EndLoop => self.end_loop(),
-            }?;
+        let mut writer = BufWriter::new(AutoNewlineWriter::new(write_destination));
+        self.run(read_source, &mut writer)
+            .map_err(|e| self.with_state(e))
+    }
+
+    /// Start over, as if the [VM] was just built with the same settings: its memory is back to as many zero cells as
+    /// it had at first, and the pointer, program counter, step count and pulled I/O are back to where they were. The
+    /// optimized program is kept, so that the same program can be interpreted again and again without lowering it
+    /// again. A cancelled [VM] stays cancelled.
+    /// # Example
+    /// ```
+    /// # use bf_interp::*;
+    /// use std::io::empty;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// let program = Program::new("", ">+.").validated().unwrap();
+    /// let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), true, &program);
+    /// let mut output = vec![];
+    /// virtual_machine.interpret(&mut empty(), &mut output).unwrap();
+    /// virtual_machine.reset();
+    /// assert_eq!(virtual_machine.memory(), &[0]);
+    /// assert_eq!((virtual_machine.pointer(), virtual_machine.program_counter()), (0, 0));
+    /// virtual_machine.interpret(&mut empty(), &mut output).unwrap();
+    /// assert_eq!(output, b"\x01\n\x01\n");
+    /// ```
+    pub fn reset(&mut self) {
+        self.memory.reset(self.memory_size);
+        self.pointer = 0;
+        self.program_counter = 0;
+        self.steps = 0;
+        self.input = InputQueue::new();
+        self.output.clear();
+        self.last_output = None;
+    }
+
+    /// Interpret the program once for each of `inputs`, starting over every time, see [VM::reset]. Gives what the
+    /// program wrote for each input, or how it failed. Unlike [VM::interpret], no newline is added at the end of the
+    /// output.
+    /// # Example
+    /// ```
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// let program = Program::new("", ",+.").validated().unwrap();
+    /// let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), false, &program);
+    /// let results = virtual_machine.run_batch([&b"a"[..], b"b", b""]);
+    /// assert_eq!(results[0].as_ref().unwrap(), b"b");
+    /// assert_eq!(results[1].as_ref().unwrap(), b"c");
+    /// assert!(results[2].is_err());
+    /// ```
+    pub fn run_batch<I>(&mut self, inputs: I) -> Vec<Result<Vec<u8>, BrainfuckRuntimeError>>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<[u8]>,
+    {
+        inputs
+            .into_iter()
+            .map(|input| {
+                self.reset();
+                let mut output = Vec::new();
+                self.run(&mut input.as_ref(), &mut output)
+                    .map(|_| output)
+                    .map_err(|e| self.with_state(e))
+            })
+            .collect()
+    }
+
+    /// Run steps until the program halts, then flush the output.
+    fn run<R: Read, W: Write>(
+        &mut self,
+        read_source: &mut R,
+        write_destination: &mut W,
+    ) -> Result<(), BrainfuckRuntimeError> {
+        let compiled = Arc::clone(self.compiled());
+        let (ops, jump_table) = (compiled.ir.ops(), compiled.jump_table.as_slice());
+        let mut checkpoint = self.steps;
+        while self.program_counter < ops.len() {
+            if self.steps >= checkpoint {
+                checkpoint = self.checkpoint()?;
+            }
+            self.steps += 1;
+            self.program_counter = self.execute(ops, jump_table, read_source, write_destination)?;
+        }
+
+        // Flush whatever the flush policy held back, any failure is blamed on the last output instruction.
+        if let Some(idx) = self.last_output {
+            write_destination.flush().map_err(|e| {
+                BrainfuckRuntimeError::CannotWriteOutputError(
+                    e,
+                    self.program.file_path().to_owned(),
+                    self.program.instructions()[idx],
+                )
+            })?;
         }
         Ok(())
     }
 
-    /// Move [VM] pointer one place to the left. Will cause a [BrainfuckRuntimeError] if the pointer is already at
-    /// position 0.
-    fn move_left(&mut self) -> Result<usize, BrainfuckRuntimeError> {
-        if self.pointer == 0 {
-            return Err(BrainfuckRuntimeError::CannotMoveLeftError(
-                self.program.file_path().to_owned(),
-                self.program.instructions()[self.program_counter],
-            ));
+    /// Run a single step, i.e., the [Op] at the program counter, and tell whether there are more. Input and output go
+    /// straight to `read_source` and `write_destination`, so unlike [VM::interpret], output is not flushed when the
+    /// program halts unless the flush policy asks for it. A `read_source` which fails with [ErrorKind::WouldBlock] gives
+    /// [StepStatus::NeedsInput] instead of an error.
+    /// # Example
+    /// ```
+    /// # use bf_interp::*;
+    /// use std::io::{empty, sink};
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_types::pass_manager::{OptLevel, PassManager};
+    /// use bf_interp::step_status::StepStatus;
+    /// let program = Program::new("", "+>+").validated().unwrap();
+    /// let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program)
+    ///     .with_passes(&PassManager::new(OptLevel::O0));
+    /// assert!(virtual_machine.step(&mut empty(), &mut sink()).is_running());
+    /// assert_eq!(virtual_machine.pointer(), 0);
+    /// assert!(virtual_machine.step(&mut empty(), &mut sink()).is_running());
+    /// assert_eq!(virtual_machine.pointer(), 1);
+    /// assert!(matches!(virtual_machine.step(&mut empty(), &mut sink()), StepStatus::Halted));
+    /// assert_eq!(virtual_machine.memory(), &[1, 1]);
+    /// ```
+    pub fn step<R: Read, W: Write>(
+        &mut self,
+        read_source: &mut R,
+        write_destination: &mut W,
+    ) -> StepStatus {
+        let compiled = Arc::clone(self.compiled());
+        let (ops, jump_table) = (compiled.ir.ops(), compiled.jump_table.as_slice());
+        if self.program_counter >= ops.len() {
+            return StepStatus::Halted;
         }
-        self.pointer -= 1;
-        Ok(self.program_counter + 1)
+        let executed = self.checkpoint().and_then(|_| {
+            self.steps += 1;
+            self.program_counter = self.execute(ops, jump_table, read_source, write_destination)?;
+            Ok(())
+        });
+        match executed {
+            Err(BrainfuckRuntimeError::CannotReadInputError(e, _, _))
+                if e.kind() == ErrorKind::WouldBlock =>
+            {
+                self.steps -= 1;
+                StepStatus::NeedsInput
+            }
+            Err(e) => StepStatus::Error(self.with_state(e)),
+            Ok(()) if self.program_counter >= ops.len() => StepStatus::Halted,
+            Ok(()) => StepStatus::Running,
+        }
+    }
+
+    /// Keep running steps, see [VM::step], until `predicate` holds right after one of them, or the [VM] stops running.
+    /// A [StepStatus::Running] means `predicate` holds.
+    /// # Example
+    /// ```
+    /// # use bf_interp::*;
+    /// use std::io::{empty, sink};
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_interp::step_status::StepStatus;
+    /// let program = Program::new("", "++++[>+<-]>").validated().unwrap();
+    /// let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+    /// let status = virtual_machine.run_until(&mut empty(), &mut sink(), |vm| vm.memory()[0] == 0);
+    /// assert!(status.is_running());
+    /// assert_eq!(virtual_machine.memory(), &[0, 4]);
+    /// assert_eq!(virtual_machine.pointer(), 0);
+    /// ```
+    pub fn run_until<R, W, P>(
+        &mut self,
+        read_source: &mut R,
+        write_destination: &mut W,
+        mut predicate: P,
+    ) -> StepStatus
+    where
+        R: Read,
+        W: Write,
+        P: FnMut(&Self) -> bool,
+    {
+        loop {
+            match self.step(read_source, write_destination) {
+                StepStatus::Running if !predicate(self) => continue,
+                status => return status,
+            }
+        }
+    }
+
+    /// Hands `input` to the [VM] for [VM::resume], after whatever it was handed before.
+    pub fn feed_input(&mut self, input: &[u8]) {
+        self.input.push(input);
+    }
+
+    /// Tells the [VM] that [VM::feed_input] will not be called anymore, so that reading beyond the input handed so far
+    /// is handled according to the EOF policy instead of waiting for more.
+    pub fn end_input(&mut self) {
+        self.input.close();
+    }
+
+    /// Keep running steps on the input handed by [VM::feed_input], see [VM::step], until one of them writes output,
+    /// there is no input to read yet, or the [VM] stops running. Output is kept until taken by [VM::take_output]. That
+    /// way, the [VM] never blocks and can be driven by a host which gets input and sends output whenever it can.
+    /// # Example
+    /// ```
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// use bf_interp::step_status::StepStatus;
+    /// let program = Program::new("", ",+.,+.").validated().unwrap();
+    /// let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), false, &program);
+    /// assert!(matches!(virtual_machine.resume(), StepStatus::NeedsInput));
+    ///
+    /// virtual_machine.feed_input(b"ab");
+    /// assert!(virtual_machine.resume().is_running());
+    /// assert_eq!(virtual_machine.take_output(), b"b");
+    /// assert!(matches!(virtual_machine.resume(), StepStatus::Halted));
+    /// assert_eq!(virtual_machine.take_output(), b"c");
+    /// ```
+    pub fn resume(&mut self) -> StepStatus {
+        let mut input = std::mem::take(&mut self.input);
+        let mut output = std::mem::take(&mut self.output);
+        let written = output.len();
+        let status = loop {
+            match self.step(&mut input, &mut output) {
+                StepStatus::Running if output.len() == written => continue,
+                status => break status,
+            }
+        };
+        (self.input, self.output) = (input, output);
+        status
+    }
+
+    /// Output written by [VM::resume] since it was last taken.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Run a single step, i.e., the [Op] at the program counter, which must be within `ops`, and give the program
+    /// counter of the next one. `ops` and `jump_table` are those of [VM::compiled], fetched once by the caller rather
+    /// than on every step. The caller also moves the program counter and counts the step, which keeps the hot loop of
+    /// [VM::interpret] as fast as running the [Op] right there.
+    #[inline(always)]
+    fn execute<R: Read, W: Write>(
+        &mut self,
+        ops: &[SpannedOp],
+        jump_table: &[usize],
+        read_source: &mut R,
+        write_destination: &mut W,
+    ) -> Result<usize, BrainfuckRuntimeError> {
+        let spanned_op = ops[self.program_counter];
+        let span = spanned_op.span();
+        match spanned_op.op() {
+            Op::Move(distance) => self
+                .move_by(distance, span.start())
+                .map(|_| self.program_counter + 1),
+            Op::Add { offset, amount } => self.add(offset, amount, span.start()),
+            Op::Input => self.read_value(read_source, write_destination),
+            Op::Output => self.write_value(write_destination, span.start()),
+            Op::BeginLoop => self.begin_loop(jump_table),
+            Op::EndLoop => self.end_loop(jump_table),
+            Op::SetZero => self.set_zero(),
+            Op::Scan(stride) => self.scan(stride, span.start() + 1),
+            Op::MulAdd { offset, factor } => self.multiply_add(offset, factor),
+            Op::Guard { min, max } => self.guard(min, max, span),
+        }
+    }
+
+    /// Stop the [VM] before its next step if it ran out of steps or was cancelled. Otherwise, gives the number of steps
+    /// at which to check again.
+    fn checkpoint(&self) -> Result<usize, BrainfuckRuntimeError> {
+        if self
+            .max_steps
+            .is_some_and(|max_steps| self.steps >= max_steps)
+        {
+            return Err(self.step_limit_exceeded());
+        }
+        let next = match &self.cancellation {
+            Some(token) if token.is_cancelled() => {
+                return Err(BrainfuckRuntimeError::Cancelled(
+                    self.program.file_path().to_owned(),
+                    self.source_instruction(),
+                ))
+            }
+            Some(_) => self.steps.saturating_add(CANCELLATION_INTERVAL),
+            None => usize::MAX,
+        };
+        Ok(self.max_steps.map_or(next, |max_steps| max_steps.min(next)))
+    }
+
+    /// The error for running out of steps, located at the current [Op].
+    fn step_limit_exceeded(&self) -> BrainfuckRuntimeError {
+        BrainfuckRuntimeError::StepLimitExceeded(
+            self.program.file_path().to_owned(),
+            self.source_instruction(),
+            self.steps,
+        )
+    }
+
+    /// Wrap `error` along with the current state, if asked to by [VM::with_state_context] and it has none yet.
+    fn with_state(&self, error: BrainfuckRuntimeError) -> BrainfuckRuntimeError {
+        let Some(window) = self.state_window else {
+            return error;
+        };
+        if error.state().is_some() {
+            return error;
+        }
+        let window = window.min(isize::MAX as usize) as isize;
+        let first_index = self
+            .pointer
+            .saturating_sub(window)
+            .max(self.memory.first_index());
+        let last_index = self
+            .pointer
+            .saturating_add(window)
+            .min(self.memory.last_index());
+        let cells = (first_index..=last_index)
+            .map(|index| self.memory[index].to_i128())
+            .collect();
+        let state = StateContext::new(
+            self.pointer,
+            self.program_counter,
+            self.steps,
+            first_index,
+            cells,
+        );
+        BrainfuckRuntimeError::WithState(Box::new(error), Box::new(state))
+    }
+
+    /// The first source instruction which the current [Op] was built from, used for locating errors.
+    fn source_instruction(&self) -> Instruction {
+        let span = self.ir().ops()[self.program_counter].span();
+        self.program.instructions()[span.start()]
+    }
+
+    /// Move [VM] pointer by `distance` places, a negative `distance` moves it to the left. The move is made of
+    /// `distance.abs()` source instructions starting at index `first`, so that a failed move can point to the exact
+    /// source instruction.
+    #[inline]
+    fn move_by(&mut self, distance: isize, first: usize) -> Result<(), BrainfuckRuntimeError> {
+        if distance < 0 {
+            self.move_left(distance.unsigned_abs(), first)
+        } else {
+            self.move_right(distance.unsigned_abs(), first)
+        }
+    }
+
+    /// Move [VM] pointer `distance` places to the left. If the pointer would go beyond the left most position, then
+    /// this method will either extend [VM]'s memory to the left if it's allowed, wrap the pointer around to the right
+    /// most position if the memory is circular, or stop the pointer at the left most position and return a
+    /// [BrainfuckRuntimeError] pointing to the exact source instruction which failed to move it.
+    #[inline]
+    fn move_left(&mut self, distance: usize, first: usize) -> Result<(), BrainfuckRuntimeError> {
+        let target = self.pointer - distance as isize;
+        if target >= self.memory.first_index() {
+            self.pointer = target;
+            Ok(())
+        } else {
+            self.move_beyond_left_edge(target, first)
+        }
+    }
+
+    /// [VM::move_left] to `target` which is beyond the left most position.
+    #[cold]
+    fn move_beyond_left_edge(
+        &mut self,
+        target: isize,
+        first: usize,
+    ) -> Result<(), BrainfuckRuntimeError> {
+        if self.topology == Topology::Circular {
+            self.pointer = self.memory.wrap(target);
+            return Ok(());
+        }
+
+        let grown = if self.topology.grows_left() {
+            self.memory.grow_left(target)
+        } else {
+            Ok(())
+        };
+        let first_index = self.memory.first_index();
+        if target < first_index {
+            let instruction =
+                self.program.instructions()[first + (self.pointer - first_index) as usize];
+            let file_path = self.program.file_path().to_owned();
+            self.pointer = first_index;
+            return Err(match grown {
+                Ok(()) => BrainfuckRuntimeError::CannotMoveLeftError(file_path, instruction),
+                Err(OutOfMemory) => {
+                    BrainfuckRuntimeError::MemoryLimitExceeded(file_path, instruction)
+                }
+            });
+        }
+
+        self.pointer = target;
+        Ok(())
+    }
+
+    /// Move [VM] pointer `distance` places to the right. If the pointer would go beyond the right most position, then
+    /// this method will either extend [VM]'s memory if it's allowed, wrap the pointer around to the left most position
+    /// if the memory is circular, or stop the pointer at the right most position and return a [BrainfuckRuntimeError]
+    /// saying invalid operation.
+    #[inline]
+    fn move_right(&mut self, distance: usize, first: usize) -> Result<(), BrainfuckRuntimeError> {
+        let target = self.pointer + distance as isize;
+        if target <= self.memory.last_index() {
+            self.pointer = target;
+            Ok(())
+        } else {
+            self.move_beyond_right_edge(target, first)
+        }
+    }
+
+    /// [VM::move_right] to `target` which is beyond the right most position.
+    #[cold]
+    fn move_beyond_right_edge(
+        &mut self,
+        target: isize,
+        first: usize,
+    ) -> Result<(), BrainfuckRuntimeError> {
+        if self.topology == Topology::Circular {
+            self.pointer = self.memory.wrap(target);
+            return Ok(());
+        }
+
+        let grown = if self.topology.grows_right() {
+            self.memory.grow_right(target)
+        } else {
+            Ok(())
+        };
+        let last_index = self.memory.last_index();
+        if target > last_index {
+            let instruction =
+                self.program.instructions()[first + (last_index - self.pointer) as usize];
+            let file_path = self.program.file_path().to_owned();
+            self.pointer = last_index;
+            return Err(match grown {
+                Ok(()) => BrainfuckRuntimeError::CannotMoveRightError(file_path, instruction),
+                Err(OutOfMemory) => {
+                    BrainfuckRuntimeError::MemoryLimitExceeded(file_path, instruction)
+                }
+            });
+        }
+
+        self.pointer = target;
+        Ok(())
+    }
+
+    /// Add `amount` to the cell at `offset` relative to [VM] pointer, a negative `amount` decrements it. A non-zero
+    /// `offset` is known to be accessible thanks to the preceding [Op::Guard]. The addition is made of `amount.abs()`
+    /// source instructions starting at index `first`, so that an overflow can point to the exact source instruction,
+    /// in which case the pointer is moved to the overflowing cell, as it would be without optimizations.
+    #[inline(always)]
+    fn add(
+        &mut self,
+        offset: isize,
+        amount: isize,
+        first: usize,
+    ) -> Result<usize, BrainfuckRuntimeError> {
+        let target = self.pointer + offset;
+        match (self.overflow_policy, self.memory.get_mut(target)) {
+            (OverflowPolicy::Wrapping, Ok(cell)) => {
+                cell.increment_by(amount);
+                Ok(self.program_counter + 1)
+            }
+            _ => self.checked_add(target, amount, first),
+        }
+    }
+
+    /// [VM::add] to the cell at `target` under an [OverflowPolicy] other than [OverflowPolicy::Wrapping], or when the
+    /// cell cannot be allocated, kept out of line so that the common case stays small.
+    #[inline(never)]
+    fn checked_add(
+        &mut self,
+        target: isize,
+        amount: isize,
+        first: usize,
+    ) -> Result<usize, BrainfuckRuntimeError> {
+        let Ok(cell) = self.memory.get_mut(target) else {
+            return Err(self.add_failed(target, first, None));
+        };
+
+        let steps = match self.overflow_policy {
+            OverflowPolicy::Wrapping => {
+                cell.increment_by(amount);
+                amount.unsigned_abs()
+            }
+            OverflowPolicy::Saturating => {
+                cell.saturating_increment_by(amount);
+                amount.unsigned_abs()
+            }
+            OverflowPolicy::Error => cell.saturating_increment_by(amount),
+        };
+        if steps < amount.unsigned_abs() {
+            return Err(self.add_failed(target, first, Some(steps)));
+        }
+        Ok(self.program_counter + 1)
+    }
+
+    /// The error for [VM::add] failing on the cell at `target`, i.e., overflowing it after `steps` of its source
+    /// instructions starting at index `first`, or being out of memory if none. The pointer is moved to that cell.
+    #[cold]
+    fn add_failed(
+        &mut self,
+        target: isize,
+        first: usize,
+        steps: Option<usize>,
+    ) -> BrainfuckRuntimeError {
+        self.pointer = target;
+        let file_path = self.program.file_path().to_owned();
+        match steps {
+            Some(steps) => BrainfuckRuntimeError::CellOverflowError(
+                file_path,
+                self.program.instructions()[first + steps],
+                target,
+            ),
+            None => BrainfuckRuntimeError::MemoryLimitExceeded(
+                file_path,
+                self.program.instructions()[first],
+            ),
+        }
+    }
+
+    /// Set the value pointed by [VM] pointer to zero.
+    fn set_zero(&mut self) -> Result<usize, BrainfuckRuntimeError> {
+        self.memory
+            .set(self.pointer, T::zero())
+            .map_err(|OutOfMemory| self.memory_limit_exceeded())?;
+        Ok(self.program_counter + 1)
+    }
+
+    /// Keep moving [VM] pointer by `stride` places until it points to a zero cell. `first` is the index of the first
+    /// move instruction in the loop body. Every move counts as a step of its own, so that a scan through many cells
+    /// still runs out of steps and stops once cancelled.
+    fn scan(&mut self, stride: isize, first: usize) -> Result<usize, BrainfuckRuntimeError> {
+        let mut checkpoint = self.steps;
+        while self.memory[self.pointer] != T::zero() {
+            if self.steps >= checkpoint {
+                checkpoint = self.checkpoint()?;
+            }
+            self.steps += 1;
+            self.move_by(stride, first)?;
+        }
+        Ok(self.program_counter + 1)
+    }
+
+    /// Add the value pointed by [VM] pointer multiplied by `factor` to the cell at `offset` relative to the pointer. The
+    /// target cell is known to be accessible thanks to the preceding [Op::Guard].
+    fn multiply_add(
+        &mut self,
+        offset: isize,
+        factor: isize,
+    ) -> Result<usize, BrainfuckRuntimeError> {
+        let value = self.memory[self.pointer].clone();
+        if value != T::zero() {
+            match self.memory.get_mut(self.pointer + offset) {
+                Ok(cell) => cell.multiply_add(&value, factor),
+                Err(OutOfMemory) => return Err(self.memory_limit_exceeded()),
+            }
+        }
+        Ok(self.program_counter + 1)
+    }
+
+    /// The error for failing to allocate a cell, located at the current [Op].
+    fn memory_limit_exceeded(&self) -> BrainfuckRuntimeError {
+        BrainfuckRuntimeError::MemoryLimitExceeded(
+            self.program.file_path().to_owned(),
+            self.source_instruction(),
+        )
+    }
+
+    /// Make sure cells from `min` to `max` relative to [VM] pointer are accessible, extending [VM]'s memory if it's
+    /// allowed and within its limits. Memory grows as if the source instructions in `span` moved the pointer one cell
+    /// at a time, so it ends up just as large as without optimizations. Otherwise, those source instructions are
+    /// replayed one at a time, so that the error is exactly the same as interpreting them without optimizations.
+    fn guard(
+        &mut self,
+        min: isize,
+        max: isize,
+        span: Span,
+    ) -> Result<usize, BrainfuckRuntimeError> {
+        let is_loop = self.program.instructions()[span.start()].raw_instruction()
+            == RawInstruction::BeginLoop;
+        if is_loop && self.memory[self.pointer] == T::zero() {
+            return Ok(self.program_counter + 1);
+        }
+
+        let (lowest, highest) = (self.pointer + min, self.pointer + max);
+        let left_fits = lowest >= self.memory.first_index() || self.topology.grows_left();
+        let right_fits = highest <= self.memory.last_index() || self.topology.grows_right();
+        if !left_fits || !right_fits {
+            return Err(self.replay(span));
+        }
+
+        if !self.memory.contains(lowest) || !self.memory.contains(highest) {
+            let mut pointer = self.pointer;
+            let path = self.program.instructions()[span.start()..span.end()]
+                .iter()
+                .filter_map(|ins| match ins.raw_instruction() {
+                    RawInstruction::MoveLeft => Some(-1),
+                    RawInstruction::MoveRight => Some(1),
+                    _ => None,
+                })
+                .map(|step| {
+                    pointer += step;
+                    pointer
+                });
+            if self.memory.grow_along(path).is_err() {
+                return Err(self.replay(span));
+            }
+        }
+        Ok(self.program_counter + 1)
+    }
+
+    /// Interpret the source instructions in `span` one at a time, ignoring brackets, until one of them fails. Only used
+    /// when an [Op::Guard] already knows one of them will.
+    fn replay(&mut self, span: Span) -> BrainfuckRuntimeError {
+        for idx in span.start()..span.end() {
+            let result = match self.program.instructions()[idx].raw_instruction() {
+                RawInstruction::MoveLeft => self.move_left(1, idx),
+                RawInstruction::MoveRight => self.move_right(1, idx),
+                RawInstruction::Increment => self.add(0, 1, idx).map(|_| ()),
+                RawInstruction::Decrement => self.add(0, -1, idx).map(|_| ()),
+                _ => Ok(()),
+            };
+            if let Err(e) = result {
+                return e;
+            }
+        }
+        unreachable!("an Op::Guard failed but none of the source instructions it guards did")
+    }
+
+    /// Read a u8 value from user specified reading source. Anything beyond a byte-long would be ignored. The output
+    /// written so far is flushed first if the flush policy asks for it, so that prompts are shown. Running out of input
+    /// is handled according to the EOF policy.
+    fn read_value<R: Read, W: Write>(
+        &mut self,
+        input_source: &mut R,
+        write_destination: &mut W,
+    ) -> Result<usize, BrainfuckRuntimeError> {
+        if self.flush_policy.flush_before_input() {
+            write_destination.flush().map_err(|e| {
+                BrainfuckRuntimeError::CannotWriteOutputError(
+                    e,
+                    self.program.file_path().to_owned(),
+                    self.source_instruction(),
+                )
+            })?;
+        }
+
+        let mut buf = [0; 1];
+        let stored = match input_source.read_exact(&mut buf) {
+            Ok(()) => self
+                .memory
+                .get_mut(self.pointer)
+                .map(|cell| cell.set_value(buf[0])),
+            Err(e)
+                if e.kind() == ErrorKind::UnexpectedEof && self.eof_policy != EofPolicy::Error =>
+            {
+                match self.eof_policy {
+                    EofPolicy::Zero => self.memory.set(self.pointer, T::zero()),
+                    EofPolicy::MinusOne => self.memory.get_mut(self.pointer).map(|cell| {
+                        *cell = T::zero();
+                        cell.decrement();
+                    }),
+                    EofPolicy::Error | EofPolicy::Unchanged => Ok(()),
+                }
+            }
+            Err(e) => {
+                return Err(BrainfuckRuntimeError::CannotReadInputError(
+                    e,
+                    self.program.file_path().to_owned(),
+                    self.source_instruction(),
+                ))
+            }
+        };
+        stored.map_err(|OutOfMemory| self.memory_limit_exceeded())?;
+
+        Ok(self.program_counter + 1)
+    }
+
+    /// Write a cell value as ASCII to user specified write destination, and flush it if the flush policy asks for it.
+    /// `first` is the index of the output source instruction, which a later failure to flush is blamed on.
+    fn write_value<W: Write>(
+        &mut self,
+        write_destination: &mut W,
+        first: usize,
+    ) -> Result<usize, BrainfuckRuntimeError> {
+        self.last_output = Some(first);
+        let value = self.memory()[self.pointer].get_value();
+        write_destination.write_all(&[value]).map_err(|e| {
+            BrainfuckRuntimeError::CannotWriteOutputError(
+                e,
+                self.program.file_path().to_owned(),
+                self.source_instruction(),
+            )
+        })?;
+
+        if !self.flush_policy.flush_after(value) {
+            return Ok(self.program_counter + 1);
+        }
+        write_destination.flush().map_err(|e| {
+            BrainfuckRuntimeError::CannotWriteOutputError(
+                e,
+                self.program.file_path().to_owned(),
+                self.source_instruction(),
+            )
+        })?;
+
+        Ok(self.program_counter + 1)
+    }
+
+    /// Start a loop for Brainfuck code.
+    fn begin_loop(&mut self, jump_table: &[usize]) -> Result<usize, BrainfuckRuntimeError> {
+        if self.memory[self.pointer] == T::zero() {
+            Ok(jump_table[self.program_counter] + 1)
+        } else {
+            Ok(self.program_counter + 1)
+        }
+    }
+
+    /// End the current Brainfuck code loop.
+    fn end_loop(&mut self, jump_table: &[usize]) -> Result<usize, BrainfuckRuntimeError> {
+        if self.memory[self.pointer] != T::zero() {
+            Ok(jump_table[self.program_counter] + 1)
+        } else {
+            Ok(self.program_counter + 1)
+        }
+    }
+
+    /// Getter.
+    pub fn memory(&self) -> &Tape<T> {
+        &self.memory
+    }
+
+    /// If the memory can extend in any direction.
+    pub fn can_extend(&self) -> bool {
+        self.topology.grows_left() || self.topology.grows_right()
+    }
+
+    /// Getter.
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Getter.
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.flush_policy
+    }
+
+    /// Getter.
+    pub fn eof_policy(&self) -> EofPolicy {
+        self.eof_policy
+    }
+
+    /// Getter.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        self.overflow_policy
+    }
+
+    /// Number of steps run so far, see [VM::with_max_steps].
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Getter.
+    pub fn max_steps(&self) -> Option<usize> {
+        self.max_steps
+    }
+
+    /// Getter.
+    pub fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    /// How many cells on each side of the pointer errors carry, see [VM::with_state_context].
+    pub fn state_context(&self) -> Option<usize> {
+        self.state_window
+    }
+
+    /// The [ValidatedProgram] this [VM] interprets.
+    pub fn program(&self) -> &ValidatedProgram {
+        &self.program
+    }
+
+    /// Index of the [Op] of [VM::ir] to run next, the program has halted once it is beyond the last one.
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    /// Index of the memory cell the pointer is at, see [Tape] about indices.
+    pub fn pointer(&self) -> isize {
+        self.pointer
+    }
+
+    /// The first source instruction of the [Op] to run next, if the program has not halted yet.
+    pub fn current_instruction(&self) -> Option<Instruction> {
+        self.ir()
+            .ops()
+            .get(self.program_counter)
+            .map(|spanned_op| self.program.instructions()[spanned_op.span().start()])
+    }
+
+    /// The optimized program this [VM] runs, one step per [Op].
+    pub fn ir(&self) -> &Ir {
+        &self.compiled().ir
+    }
+
+    /// Describes `error` as a [Diagnostic], along with where the pointer is and the value of its cell. `error` has to
+    /// be the last error this [VM] ran into, so that it is still in the state the error left it in.
+    /// # Example
+    /// ```
+    /// # use bf_interp::*;
+    /// use std::num::NonZeroUsize;
+    /// use bf_types::Program;
+    /// let program = Program::new("", "+++<").validated().unwrap();
+    /// let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), false, &program);
+    /// let error = virtual_machine.interpret(&mut std::io::empty(), &mut std::io::sink()).unwrap_err();
+    /// let diagnostic = virtual_machine.diagnostic(&error);
+    /// assert_eq!(diagnostic.code(), "E0101");
+    /// assert_eq!(diagnostic.message(), "pointer already at the left edge of the memory");
+    /// assert_eq!((diagnostic.pointer(), diagnostic.cell()), (Some(0), Some(3)));
+    /// ```
+    pub fn diagnostic(&self, error: &BrainfuckRuntimeError) -> Diagnostic {
+        let diagnostic = error.diagnostic(&self.program).with_pointer(self.pointer);
+        match self.memory.get(self.pointer) {
+            Some(cell) => diagnostic.with_cell(cell.to_i128()),
+            None => diagnostic,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bf_types::pass_manager::OptLevel;
+    use bf_types::Program;
+    use eof_policy::EofPolicy;
+    use flush_policy::FlushPolicy;
+    use overflow_policy::OverflowPolicy;
+    use std::io::{stdin, stdout, Cursor};
+
+    /// Should create a VM with specified number of cells in memory.
+    #[test]
+    fn specified_memory_size() {
+        let program = Program::new("", "").validated().unwrap();
+        let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), true, &program);
+        assert_eq!(virtual_machine.memory().len(), 10);
+    }
+
+    /// Should initialize pointer location to 0.
+    #[test]
+    fn initialize_pointer_location() {
+        let program = Program::new("", "").validated().unwrap();
+        let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), true, &program);
+        assert_eq!(virtual_machine.pointer, 0);
+    }
+
+    /// Should successfully move pointer one place to the left.
+    #[test]
+    fn successfully_move_pointer_left() {
+        let program = Program::new("", "<").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), false, &program);
+        virtual_machine.pointer = 1;
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.pointer, 0);
+    }
+
+    /// Should failed moving pointer one place to the left.
+    #[test]
+    fn unsuccessfully_move_pointer_left() {
+        let program = Program::new("", "<").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), false, &program);
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        match result {
+            Err(BrainfuckRuntimeError::CannotMoveLeftError(file_path, ins)) => {
+                assert_eq!(file_path.to_str().unwrap(), "");
+                assert_eq!(ins, Instruction::new(1, 1, RawInstruction::MoveLeft));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+
+        assert_eq!(virtual_machine.pointer, 0);
+    }
+
+    /// Should successfully move pointer one place to the right in normal case.
+    #[test]
+    fn successfully_move_pointer_right_normal_case() {
+        let program = Program::new("", ">").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), false, &program);
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.pointer, 1);
+    }
+
+    /// Should successfully move pointer one place to the right when extension is allowed and needed.
+    #[test]
+    fn successfully_move_pointer_right_at_right_edge() {
+        let program = Program::new("", ">").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), true, &program);
+        virtual_machine.pointer = 1;
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.pointer, 2);
+        assert_eq!(virtual_machine.memory.len(), 4);
+    }
+
+    /// Should failed moving pointer one place to the right when at the right edge and cannot extend.
+    #[test]
+    fn unsuccessfully_move_pointer_right() {
+        let program = Program::new("", ">").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+        virtual_machine.pointer = 1;
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        match result {
+            Err(BrainfuckRuntimeError::CannotMoveRightError(file_path, ins)) => {
+                assert_eq!(file_path.to_str().unwrap(), "");
+                assert_eq!(ins, Instruction::new(1, 1, RawInstruction::MoveRight));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+
+        assert_eq!(virtual_machine.pointer, 1);
+    }
+
+    /// Should report the exact instruction in a folded run which moved the pointer beyond the left edge.
+    #[test]
+    fn unsuccessfully_move_pointer_left_in_a_run() {
+        let program = Program::new("", "<<<").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), false, &program);
+        virtual_machine.pointer = 1;
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        match result {
+            Err(BrainfuckRuntimeError::CannotMoveLeftError(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 2, RawInstruction::MoveLeft));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+
+        assert_eq!(virtual_machine.pointer, 0);
+    }
+
+    /// Should report the exact instruction in a folded run which moved the pointer beyond the right edge.
+    #[test]
+    fn unsuccessfully_move_pointer_right_in_a_run() {
+        let program = Program::new("", ">>>").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(3).unwrap(), false, &program);
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        match result {
+            Err(BrainfuckRuntimeError::CannotMoveRightError(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 3, RawInstruction::MoveRight));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+
+        assert_eq!(virtual_machine.pointer, 2);
+    }
+
+    /// Should keep doubling memory size until a folded run of moves fits in.
+    #[test]
+    fn successfully_move_pointer_right_in_a_run_beyond_right_edge() {
+        let program = Program::new("", ">>>>>").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), true, &program);
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.pointer, 5);
+        assert_eq!(virtual_machine.memory.len(), 8);
+    }
+
+    /// Should grow memory to the left when it's allowed, addressing new cells with negative indices.
+    #[test]
+    fn successfully_move_pointer_left_beyond_left_edge() {
+        let program = Program::new("", "<+<<<+>>+>[>+<-]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program)
+            .with_topology(Topology::Bidirectional);
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.pointer, -1);
+        assert_eq!(virtual_machine.memory().first_index(), -6);
+        assert_eq!(virtual_machine.memory()[-4], 1);
+        assert_eq!(virtual_machine.memory()[-2], 1);
+        assert_eq!(virtual_machine.memory()[-1], 0);
+        assert_eq!(virtual_machine.memory()[0], 1);
+    }
+
+    /// Should fail exactly where extending memory would go beyond the memory limit, with the pointer at the edge.
+    #[test]
+    fn unsuccessfully_extend_memory_beyond_limit() {
+        let program = Program::new("", ">>>>>").validated().unwrap();
+        let mut virtual_machine: VM<u8> =
+            VM::new(NonZeroUsize::new(2).unwrap(), true, &program).with_memory_limit(Some(4));
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        match result {
+            Err(BrainfuckRuntimeError::MemoryLimitExceeded(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 4, RawInstruction::MoveRight));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+        assert_eq!(virtual_machine.pointer, 3);
+        assert_eq!(virtual_machine.memory().len(), 4);
+    }
+
+    /// Should grow memory exactly as needed, and fail exactly where it would have more than the max number of cells.
+    #[test]
+    fn unsuccessfully_extend_memory_beyond_max_cells() {
+        let program = Program::new("", ">>+>>>").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), true, &program)
+            .with_growth_policy(GrowthPolicy::Exact)
+            .with_max_cells(Some(4));
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        match result {
+            Err(BrainfuckRuntimeError::MemoryLimitExceeded(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 5, RawInstruction::MoveRight));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+        assert_eq!(virtual_machine.pointer, 3);
+        assert_eq!(virtual_machine.memory(), &[0, 0, 1, 0]);
+    }
+
+    /// Should stop right before the first step beyond the limit, even when the program would run forever.
+    #[test]
+    fn run_out_of_steps() {
+        let program = Program::new("", ">>>>>").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), true, &program)
+            .with_passes(&PassManager::new(OptLevel::O0))
+            .with_max_steps(Some(3));
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        match result {
+            Err(BrainfuckRuntimeError::StepLimitExceeded(_, ins, steps)) => {
+                assert_eq!(ins, Instruction::new(1, 4, RawInstruction::MoveRight));
+                assert_eq!(steps, 3);
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+        assert_eq!(virtual_machine.pointer, 3);
+
+        let program = Program::new("", "+[]").validated().unwrap();
+        let mut virtual_machine: VM<u8> =
+            VM::new(NonZeroUsize::new(1).unwrap(), false, &program).with_max_steps(Some(1000));
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+        assert!(matches!(
+            result,
+            Err(BrainfuckRuntimeError::StepLimitExceeded(_, _, 1000))
+        ));
+        assert_eq!(virtual_machine.steps(), 1000);
+    }
+
+    /// Should stop a program which would run forever once cancelled from another thread, keeping its state.
+    #[test]
+    fn cancel_from_another_thread() {
+        let program = Program::new("", "+[>+<]").validated().unwrap();
+        let token = CancellationToken::new();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program)
+            .with_cancellation(token.clone());
+        let handle = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            token.cancel();
+        });
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+        handle.join().unwrap();
+
+        assert!(matches!(
+            result,
+            Err(BrainfuckRuntimeError::Cancelled(_, _))
+        ));
+        assert_eq!(virtual_machine.memory()[0], 1);
+        assert!(virtual_machine.steps() > 0);
+
+        let program = Program::new("", "+").validated().unwrap();
+        let token = CancellationToken::new();
+        token.cancel();
+        let mut virtual_machine: VM<u8> =
+            VM::new(NonZeroUsize::new(1).unwrap(), false, &program).with_cancellation(token);
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+        assert!(matches!(
+            result,
+            Err(BrainfuckRuntimeError::Cancelled(_, _))
+        ));
+        assert_eq!(virtual_machine.memory(), &[0]);
+    }
+
+    /// Should stop a scan once cancelled, rather than only after it.
+    #[test]
+    fn cancel_in_a_scan() {
+        /// Gives a byte of input, cancelling its token while doing so.
+        struct CancellingReader(CancellationToken);
+
+        impl Read for CancellingReader {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                self.0.cancel();
+                buf[0] = 1;
+                Ok(1)
+            }
+        }
+
+        let program = Program::new("", ",[>]").validated().unwrap();
+        let token = CancellationToken::new();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(4).unwrap(), false, &program)
+            .with_cancellation(token.clone());
+        virtual_machine.memory[1] = 1;
+        let result = virtual_machine.interpret(&mut CancellingReader(token), &mut stdout());
+
+        match result {
+            Err(BrainfuckRuntimeError::Cancelled(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 2, RawInstruction::BeginLoop));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+        assert_eq!(virtual_machine.pointer, 0);
+    }
+
+    /// Should wait for input without running anything, and stay at a failed step.
+    #[test]
+    fn step_through_program() {
+        /// An input source which has no input until some is pushed to it.
+        struct PendingInput(Vec<u8>);
+        impl Read for PendingInput {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                if self.0.is_empty() {
+                    return Err(ErrorKind::WouldBlock.into());
+                }
+                buf[0] = self.0.remove(0);
+                Ok(1)
+            }
+        }
+
+        let program = Program::new("", ",.<").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), false, &program);
+        let mut input = PendingInput(vec![]);
+        let mut output = vec![];
+
+        let status = virtual_machine.step(&mut input, &mut output);
+        assert!(matches!(status, StepStatus::NeedsInput));
+        assert_eq!(virtual_machine.steps(), 0);
+        assert_eq!(
+            virtual_machine.current_instruction(),
+            Some(Instruction::new(1, 1, RawInstruction::Input))
+        );
+
+        input.0.push(b'a');
+        let status = virtual_machine.run_until(&mut input, &mut output, |vm| {
+            vm.current_instruction().map(|ins| ins.raw_instruction())
+                == Some(RawInstruction::MoveLeft)
+        });
+        assert!(status.is_running());
+        assert_eq!(output, b"a");
+        assert_eq!(virtual_machine.program_counter(), 2);
+
+        for _ in 0..2 {
+            let status = virtual_machine.step(&mut input, &mut output);
+            assert!(matches!(
+                status,
+                StepStatus::Error(BrainfuckRuntimeError::CannotMoveLeftError(_, _))
+            ));
+            assert_eq!(virtual_machine.program_counter(), 2);
+        }
+    }
+
+    /// Should wait for input handed bit by bit, and apply the EOF policy only once input has ended.
+    #[test]
+    fn resume_on_pulled_input() {
+        let program = Program::new("", ",[.,]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), false, &program)
+            .with_eof_policy(EofPolicy::Zero);
+        virtual_machine.feed_input(b"h");
+
+        let mut output = vec![];
+        let status = loop {
+            match virtual_machine.resume() {
+                StepStatus::Running => output.extend(virtual_machine.take_output()),
+                status => break status,
+            }
+        };
+        assert!(matches!(status, StepStatus::NeedsInput));
+        assert_eq!(output, b"h");
+
+        virtual_machine.feed_input(b"i");
+        virtual_machine.end_input();
+        let status = loop {
+            match virtual_machine.resume() {
+                StepStatus::Running => output.extend(virtual_machine.take_output()),
+                status => break status,
+            }
+        };
+        assert!(matches!(status, StepStatus::Halted));
+        assert_eq!(output, b"hi");
+        assert_eq!(virtual_machine.memory(), &[0]);
+    }
+
+    /// Should interpret a shared program on another thread.
+    #[test]
+    fn interpret_shared_program_on_another_thread() {
+        let program = Arc::new(Program::new("", "++[>+++<-]>.").validated().unwrap());
+        let mut virtual_machine: VM<'static, u8> =
+            VM::with_shared_program(NonZeroUsize::new(2).unwrap(), false, program.clone());
+        let output = std::thread::spawn(move || {
+            let mut output = vec![];
+            virtual_machine
+                .interpret(&mut stdin(), &mut output)
+                .map(|_| output)
+        })
+        .join()
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(output, [6, b'\n']);
+        assert_eq!(Arc::strong_count(&program), 1);
+    }
+
+    /// Should tell the state the VM was in when running into an error, but only when asked to.
+    #[test]
+    fn carry_state_context() {
+        let program = Program::new("", "+++>++<<").validated().unwrap();
+        let mut virtual_machine: VM<i16> = VM::new(NonZeroUsize::new(10).unwrap(), false, &program)
+            .with_passes(&PassManager::new(OptLevel::O0));
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+        assert!(result.unwrap_err().state().is_none());
+
+        virtual_machine.reset();
+        virtual_machine = virtual_machine.with_state_context(Some(2));
+        let error = virtual_machine
+            .interpret(&mut stdin(), &mut stdout())
+            .unwrap_err();
+        assert!(matches!(
+            error.inner(),
+            BrainfuckRuntimeError::CannotMoveLeftError(_, _)
+        ));
+        assert_eq!(error.code(), "E0101");
+        assert_eq!(
+            error.instruction(),
+            Instruction::new(1, 8, RawInstruction::MoveLeft)
+        );
+        assert_eq!(
+            error.state(),
+            Some(&StateContext::new(0, 7, 8, 0, vec![3, 2, 0]))
+        );
+    }
+
+    /// Should run every input from a clean state, even after one grew the memory or failed.
+    #[test]
+    fn run_batch_from_clean_state() {
+        let program = Program::new("", ">,[>,]<[.<]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), true, &program)
+            .with_eof_policy(EofPolicy::Zero)
+            .with_max_steps(Some(50));
+        let results = virtual_machine.run_batch(["abc", &"x".repeat(30), "d"]);
+
+        assert_eq!(results[0].as_ref().unwrap(), b"cba");
+        assert!(matches!(
+            results[1],
+            Err(BrainfuckRuntimeError::StepLimitExceeded(_, _, 50))
+        ));
+        assert_eq!(results[2].as_ref().unwrap(), b"d");
+
+        virtual_machine.reset();
+        assert_eq!(virtual_machine.memory(), &[0]);
+        assert_eq!(virtual_machine.steps(), 0);
     }
 
-    /// Move [VM] pointer one place to the right. If the pointer is already at the right most position, then this method
-    /// will either double [VM]'s memory size if it's allowed, or return a [BrainfuckRuntimeError] saying invalid
-    /// operation.
-    fn move_right(&mut self) -> Result<usize, BrainfuckRuntimeError> {
-        let memory_size = self.memory.len();
+    /// Should walk far away on a sparse memory, and fail only when writing to one page too many.
+    #[test]
+    fn allocate_sparse_memory_within_limit() {
+        let program = Program::new("", "+[>>>>>>>>>>>>>>>>+]")
+            .validated()
+            .unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), true, &program)
+            .with_backend(Backend::Sparse)
+            .with_memory_limit(Some(tape::PAGE_SIZE * 2));
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
-        if self.pointer == memory_size - 1 && !self.can_extend {
-            return Err(BrainfuckRuntimeError::CannotMoveRightError(
-                self.program.file_path().to_owned(),
-                self.program.instructions()[self.program_counter],
-            ));
-        } else if self.pointer == memory_size - 1 {
-            self.memory.resize(2 * memory_size, T::zero());
+        match result {
+            Err(BrainfuckRuntimeError::MemoryLimitExceeded(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 19, RawInstruction::Increment));
+            }
+            _ => panic!("Unrecognized error type"),
         }
-
-        self.pointer += 1;
-        Ok(self.program_counter + 1)
+        assert_eq!(virtual_machine.pointer, 2 * tape::PAGE_SIZE as isize);
+        assert_eq!(
+            virtual_machine.memory().allocated_bytes(),
+            tape::PAGE_SIZE * 2
+        );
     }
 
-    /// Increment the value pointed by [VM] pointer.
-    fn increment(&mut self) -> Result<usize, BrainfuckRuntimeError> {
-        self.memory[self.pointer].increment();
-        Ok(self.program_counter + 1)
-    }
+    /// Should wrap the pointer around both edges of a circular memory.
+    #[test]
+    fn move_pointer_around_circular_memory() {
+        let program = Program::new("", "<+<<<<+>>>>>>>>>>>>+[>>+<<-]")
+            .validated()
+            .unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(3).unwrap(), false, &program)
+            .with_topology(Topology::Circular);
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
-    /// Decrement the value pointed by [VM] pointer.
-    fn decrement(&mut self) -> Result<usize, BrainfuckRuntimeError> {
-        self.memory[self.pointer].decrement();
-        Ok(self.program_counter + 1)
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.memory(), &[2, 0, 1]);
+        assert_eq!(virtual_machine.pointer, 1);
     }
 
-    /// Read a u8 value from user specified reading source. Anything beyond a byte-long would be ignored.
-    fn read_value<R: Read>(
-        &mut self,
-        input_source: &mut R,
-    ) -> Result<usize, BrainfuckRuntimeError> {
-        let mut buf = [0; 1];
-        input_source.read_exact(&mut buf).map_err(|e| {
-            BrainfuckRuntimeError::CannotReadInputError(
-                e,
-                self.program.file_path().to_owned(),
-                self.program.instructions()[self.program_counter],
-            )
-        })?;
-
-        self.memory[self.pointer].set_value(buf[0]);
+    /// Should run out of steps rather than scan forever around a circular memory without a zero cell.
+    #[test]
+    fn scan_around_circular_memory_without_zero_cell() {
+        let program = Program::new("", "+>+>+[>]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(3).unwrap(), false, &program)
+            .with_topology(Topology::Circular)
+            .with_max_steps(Some(100));
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
-        Ok(self.program_counter + 1)
+        assert!(matches!(
+            result,
+            Err(BrainfuckRuntimeError::StepLimitExceeded(_, _, 100))
+        ));
+        assert_eq!(virtual_machine.memory(), &[1, 1, 1]);
     }
 
-    /// Write a cell value as ASCII to user specified write destination.
-    fn write_value<W: Write>(
-        &self,
-        write_destination: &mut W,
-    ) -> Result<usize, BrainfuckRuntimeError> {
-        let value = self.memory()[self.pointer].get_value();
-        write_destination.write(&[value]).map_err(|e| {
-            BrainfuckRuntimeError::CannotWriteOutputError(
-                e,
-                self.program.file_path().to_owned(),
-                self.program.instructions()[self.program_counter],
-            )
-        })?;
-
-        write_destination.flush().map_err(|e| {
-            BrainfuckRuntimeError::CannotWriteOutputError(
-                e,
-                self.program.file_path().to_owned(),
-                self.program.instructions()[self.program_counter],
-            )
-        })?;
+    /// Should increment cell value by one.
+    #[test]
+    fn increase_by_one() {
+        let program = Program::new("", "+").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
-        Ok(self.program_counter + 1)
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.memory()[0], 1_u8);
     }
 
-    /// Start a loop for Brainfuck code.
-    fn begin_loop(&mut self) -> Result<usize, BrainfuckRuntimeError> {
-        if self.memory[self.pointer] == T::zero() {
-            Ok(self.open_to_close.get(&self.program_counter).unwrap() + 1)
-        } else {
-            Ok(self.program_counter + 1)
-        }
-    }
+    /// Should go beyond upper bound if cell value is already max.
+    #[test]
+    fn go_beyond_upper_bound() {
+        let program = Program::new("", "+").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+        virtual_machine.memory[0] = 255_u8;
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
-    /// End the current Brainfuck code loop.
-    fn end_loop(&mut self) -> Result<usize, BrainfuckRuntimeError> {
-        // Ok(*self.close_to_open.get(&self.program_counter).unwrap())
-        if self.memory[self.pointer] != T::zero() {
-            Ok(self.close_to_open.get(&self.program_counter).unwrap() + 1)
-        } else {
-            Ok(self.program_counter + 1)
-        }
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.memory[0], 0_u8);
     }
 
-    /// Getter.
-    pub fn memory(&self) -> &[T] {
-        &self.memory
-    }
+    /// Should go beyond lower bound if cell value is already min.
+    #[test]
+    fn go_beyond_lower_bound() {
+        let program = Program::new("", "-").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+        virtual_machine.memory[0] = 0_u8;
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
-    /// Getter.
-    pub fn can_extend(&self) -> bool {
-        self.can_extend
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.memory[0], 255_u8);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use bf_types::Instruction;
-    use std::io::{stdin, stdout, Cursor};
+    /// Should wrap around when a folded run of increments goes beyond upper bound.
+    #[test]
+    fn go_beyond_upper_bound_in_a_run() {
+        let program = Program::new("", "+++").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+        virtual_machine.memory[0] = 254_u8;
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
-    /// Should create a VM with specified number of cells in memory.
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.memory[0], 1_u8);
+    }
+
+    /// Should decrement cell value by one.
     #[test]
-    fn specified_memory_size() {
-        let program = Program::new("", "");
-        let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), true, &program);
-        assert_eq!(virtual_machine.memory().len(), 10);
+    fn decrease_by_one() {
+        let program = Program::new("", "-").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+        virtual_machine.memory[0] = 1;
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.memory()[0], 0_u8);
     }
 
-    /// Should initialize pointer location to 0.
+    /// Should set cell value to zero with a clear loop.
     #[test]
-    fn initialize_pointer_location() {
-        let program = Program::new("", "");
-        let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), true, &program);
-        assert_eq!(virtual_machine.pointer, 0);
+    fn clear_cell() {
+        let program = Program::new("", "[-]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+        virtual_machine.memory[0] = 5;
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.memory()[0], 0_u8);
     }
 
-    /// Should successfully move pointer one place to the left.
+    /// Should stop scanning at the first zero cell.
     #[test]
-    fn successfully_move_pointer_left() {
-        let program = Program::new("", "<");
-        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), false, &program);
-        virtual_machine.pointer = 1;
+    fn scan_to_zero_cell() {
+        let program = Program::new("", "[>]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(4).unwrap(), false, &program);
+        virtual_machine.memory[0] = 1;
+        virtual_machine.memory[1] = 1;
         let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
         assert!(result.is_ok());
-        assert_eq!(virtual_machine.pointer, 0);
+        assert_eq!(virtual_machine.pointer, 2);
     }
 
-    /// Should failed moving pointer one place to the left.
+    /// Should run out of steps partway through a scan, with the pointer at the last cell it reached.
     #[test]
-    fn unsuccessfully_move_pointer_left() {
-        let program = Program::new("", "<");
-        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), false, &program);
+    fn run_out_of_steps_in_a_scan() {
+        let program = Program::new("", "[>]").validated().unwrap();
+        let mut virtual_machine: VM<u8> =
+            VM::new(NonZeroUsize::new(8).unwrap(), false, &program).with_max_steps(Some(4));
+        for idx in 0..6 {
+            virtual_machine.memory[idx] = 1;
+        }
         let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
         match result {
-            Err(BrainfuckRuntimeError::CannotMoveLeftError(file_path, ins)) => {
-                assert_eq!(file_path.to_str().unwrap(), "");
-                assert_eq!(ins, Instruction::new(1, 1, RawInstruction::MoveLeft));
+            Err(BrainfuckRuntimeError::StepLimitExceeded(_, ins, steps)) => {
+                assert_eq!(ins, Instruction::new(1, 1, RawInstruction::BeginLoop));
+                assert_eq!(steps, 4);
             }
             _ => panic!("Unrecognized error type"),
         }
-
-        assert_eq!(virtual_machine.pointer, 0);
+        assert_eq!(virtual_machine.pointer, 3);
     }
 
-    /// Should successfully move pointer one place to the right in normal case.
+    /// Should report the exact instruction in a scan loop which moved the pointer beyond the right edge.
     #[test]
-    fn successfully_move_pointer_right_normal_case() {
-        let program = Program::new("", ">");
-        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(10).unwrap(), false, &program);
+    fn unsuccessfully_scan_beyond_right_edge() {
+        let program = Program::new("", "[>>]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+        virtual_machine.memory[0] = 1;
+        virtual_machine.memory[1] = 1;
         let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
-        assert!(result.is_ok());
+
+        match result {
+            Err(BrainfuckRuntimeError::CannotMoveRightError(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 3, RawInstruction::MoveRight));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+
         assert_eq!(virtual_machine.pointer, 1);
     }
 
-    /// Should successfully move pointer one place to the right when extension is allowed and needed.
+    /// Should multiply the pointed cell into other cells.
     #[test]
-    fn successfully_move_pointer_right_at_right_edge() {
-        let program = Program::new("", ">");
-        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), true, &program);
-        virtual_machine.pointer = 1;
+    fn multiply_cell() {
+        let program = Program::new("", "[->+>++<<]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(3).unwrap(), false, &program);
+        virtual_machine.memory[0] = 3;
         let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
         assert!(result.is_ok());
-        assert_eq!(virtual_machine.pointer, 2);
-        assert_eq!(virtual_machine.memory.len(), 4);
+        assert_eq!(virtual_machine.memory(), &[0, 3, 6]);
+        assert_eq!(virtual_machine.pointer, 0);
     }
 
-    /// Should failed moving pointer one place to the right when at the right edge and cannot extend.
+    /// Should fail a multiply loop exactly where its first iteration moves the pointer beyond the right edge.
     #[test]
-    fn unsuccessfully_move_pointer_right() {
-        let program = Program::new("", ">");
+    fn unsuccessfully_multiply_beyond_right_edge() {
+        let program = Program::new("", "[->+>++<<]").validated().unwrap();
         let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
-        virtual_machine.pointer = 1;
+        virtual_machine.memory[0] = 3;
         let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
         match result {
-            Err(BrainfuckRuntimeError::CannotMoveRightError(file_path, ins)) => {
-                assert_eq!(file_path.to_str().unwrap(), "");
-                assert_eq!(ins, Instruction::new(1, 1, RawInstruction::MoveRight));
+            Err(BrainfuckRuntimeError::CannotMoveRightError(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 5, RawInstruction::MoveRight));
             }
             _ => panic!("Unrecognized error type"),
         }
 
+        assert_eq!(virtual_machine.memory(), &[2, 1]);
         assert_eq!(virtual_machine.pointer, 1);
     }
 
-    /// Should increment cell value by one.
+    /// Should skip a multiply loop on a zero cell even if it would move the pointer beyond the right edge.
     #[test]
-    fn increase_by_one() {
-        let program = Program::new("", "+");
-        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
+    fn skip_multiply_on_zero_cell() {
+        let program = Program::new("", "[->+<]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(1).unwrap(), false, &program);
         let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
         assert!(result.is_ok());
-        assert_eq!(virtual_machine.memory()[0], 1_u8);
+        assert_eq!(virtual_machine.memory().len(), 1);
     }
 
-    /// Should go beyond upper bound if cell value is already max.
+    /// Should add to cells at offsets and only move the pointer by the net distance.
     #[test]
-    fn go_beyond_upper_bound() {
-        let program = Program::new("", "+");
-        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
-        virtual_machine.memory[0] = 255_u8;
+    fn add_at_offsets() {
+        let program = Program::new("", ">+>++<").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(3).unwrap(), false, &program);
         let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
         assert!(result.is_ok());
-        assert_eq!(virtual_machine.memory[0], 0_u8);
+        assert_eq!(virtual_machine.memory(), &[0, 1, 2]);
+        assert_eq!(virtual_machine.pointer, 1);
     }
 
-    /// Should go beyond lower bound if cell value is already min.
+    /// Should fail exactly where moves replaced by offsets would have moved the pointer beyond the right edge.
     #[test]
-    fn go_beyond_lower_bound() {
-        let program = Program::new("", "-");
+    fn unsuccessfully_add_at_offset_beyond_right_edge() {
+        let program = Program::new("", ">+>+<<").validated().unwrap();
         let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
-        virtual_machine.memory[0] = 0_u8;
         let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
-        assert!(result.is_ok());
-        assert_eq!(virtual_machine.memory[0], 255_u8);
+        match result {
+            Err(BrainfuckRuntimeError::CannotMoveRightError(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 3, RawInstruction::MoveRight));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+
+        assert_eq!(virtual_machine.memory(), &[0, 1]);
+        assert_eq!(virtual_machine.pointer, 1);
     }
 
-    /// Should decrement cell value by one.
+    /// Should fail where moves replaced by offsets would have moved the pointer beyond the left edge, even if no cell
+    /// out of the edge is changed.
     #[test]
-    fn decrease_by_one() {
-        let program = Program::new("", "-");
+    fn unsuccessfully_move_beyond_left_edge_without_changing_cells() {
+        let program = Program::new("", "+<<>>+").validated().unwrap();
         let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
-        virtual_machine.memory[0] = 1;
+        virtual_machine.pointer = 1;
         let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
+        match result {
+            Err(BrainfuckRuntimeError::CannotMoveLeftError(_, ins)) => {
+                assert_eq!(ins, Instruction::new(1, 3, RawInstruction::MoveLeft));
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+
+        assert_eq!(virtual_machine.memory(), &[0, 1]);
+        assert_eq!(virtual_machine.pointer, 0);
+    }
+
+    /// Should interpret one instruction at a time at optimization level 0.
+    #[test]
+    fn interpret_without_optimization() {
+        let program = Program::new("", ">+>+<<[-]").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(3).unwrap(), false, &program)
+            .with_passes(&PassManager::new(OptLevel::O0));
+        assert_eq!(
+            virtual_machine.ir().ops().len(),
+            program.instructions().len()
+        );
+
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
         assert!(result.is_ok());
-        assert_eq!(virtual_machine.memory()[0], 0_u8);
+        assert_eq!(virtual_machine.memory(), &[0, 1, 1]);
     }
 
     /// Should successfully set a memory cell to a u8 value.
     #[test]
     fn successfully_set_memory_cell() {
-        let program = Program::new("", ",");
+        let program = Program::new("", ",").validated().unwrap();
         let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
         let mut read_source = Cursor::new(vec![65]);
         let result = virtual_machine.interpret(&mut read_source, &mut stdout());
@@ -396,7 +2045,7 @@ mod tests {
     /// Should fail due to Unexpected EOF error.
     #[test]
     fn set_memory_cell_failed_due_to_eof() {
-        let program = Program::new("", ",");
+        let program = Program::new("", ",").validated().unwrap();
         let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
         let mut read_source = Cursor::new(vec![]);
         let result = virtual_machine.interpret(&mut read_source, &mut stdout());
@@ -413,10 +2062,29 @@ mod tests {
         assert_eq!(virtual_machine.memory()[0], 0);
     }
 
+    /// Should set a memory cell according to the EOF policy once input has run out.
+    #[test]
+    fn set_memory_cell_on_eof() {
+        let cases = [
+            (EofPolicy::Unchanged, 3),
+            (EofPolicy::Zero, 0),
+            (EofPolicy::MinusOne, 255),
+        ];
+        for (eof_policy, expected) in cases {
+            let program = Program::new("", ",+++,").validated().unwrap();
+            let mut virtual_machine: VM<u8> =
+                VM::new(NonZeroUsize::new(2).unwrap(), false, &program).with_eof_policy(eof_policy);
+            let result = virtual_machine.interpret(&mut Cursor::new(vec![]), &mut stdout());
+
+            assert!(result.is_ok());
+            assert_eq!(virtual_machine.memory()[0], expected);
+        }
+    }
+
     /// Should successfully write a memory cell content to write destination.
     #[test]
     fn successfully_write_memory_cell_to_destination() {
-        let program = Program::new("", ".");
+        let program = Program::new("", ".").validated().unwrap();
         let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
         virtual_machine.memory[0] = 65;
         let mut write_destination = Cursor::new(vec![]);
@@ -426,25 +2094,158 @@ mod tests {
         assert_eq!(write_destination.into_inner(), vec![65, b'\n']);
     }
 
+    /// Should interpret wide cells without wrapping at a byte, and write their lowest byte.
+    #[test]
+    fn interpret_wide_cells() {
+        let program = Program::new("", "++++++++++++++++[>++++++++++++++++<-]>+.-[->+<]>-.")
+            .validated()
+            .unwrap();
+        let mut virtual_machine: VM<u16> = VM::new(NonZeroUsize::new(3).unwrap(), false, &program);
+        let mut write_destination = Cursor::new(vec![]);
+        let result = virtual_machine.interpret(&mut stdin(), &mut write_destination);
+
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.memory(), &[0, 0, 255]);
+        assert_eq!(write_destination.into_inner(), vec![1, 255, b'\n']);
+    }
+
+    /// Should stop cells at their bounds when saturating, even in loops which would clear a wrapping cell.
+    #[test]
+    fn saturate_cells() {
+        let program = Program::new(
+            "",
+            "++++[->+++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++++<]--",
+        )
+        .validated()
+        .unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program)
+            .with_overflow_policy(OverflowPolicy::Saturating);
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        assert!(result.is_ok());
+        assert_eq!(virtual_machine.memory(), &[0, 255]);
+    }
+
+    /// Should lower the program with every setting which changes it, whatever order they are set in.
+    #[test]
+    fn lower_with_every_setting() {
+        let program = Program::new("", "[-]>[<+>-]").validated().unwrap();
+        let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program)
+            .with_overflow_policy(OverflowPolicy::Saturating)
+            .with_passes(&PassManager::new(OptLevel::O3));
+
+        assert!(virtual_machine
+            .ir()
+            .ops()
+            .iter()
+            .all(|spanned_op| !matches!(spanned_op.op(), Op::SetZero | Op::MulAdd { .. })));
+    }
+
+    /// Should fail at the exact instruction which overflows a cell, with the pointer at that cell.
+    #[test]
+    fn overflow_cell() {
+        let program = Program::new("", "+>>+<----").validated().unwrap();
+        let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(3).unwrap(), false, &program)
+            .with_overflow_policy(OverflowPolicy::Error);
+        let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
+
+        match result {
+            Err(BrainfuckRuntimeError::CellOverflowError(_, ins, cell)) => {
+                assert_eq!(ins, Instruction::new(1, 6, RawInstruction::Decrement));
+                assert_eq!(cell, 1);
+            }
+            _ => panic!("Unrecognized error type"),
+        }
+        assert_eq!(virtual_machine.pointer, 1);
+        assert_eq!(virtual_machine.memory(), &[1, 0, 1]);
+    }
+
+    /// A write destination remembering how many bytes were written whenever it was flushed.
+    struct FlushRecorder {
+        written: Vec<u8>,
+        flushed_at: Vec<usize>,
+    }
+
+    impl Write for FlushRecorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushed_at.push(self.written.len());
+            Ok(())
+        }
+    }
+
+    /// Interpret `code` with a flush policy, and tell how many bytes were written whenever output was flushed.
+    fn flushed_at(code: &str, input: &[u8], flush_policy: FlushPolicy) -> Vec<usize> {
+        let program = Program::new("", code).validated().unwrap();
+        let mut virtual_machine: VM<u8> =
+            VM::new(NonZeroUsize::new(2).unwrap(), false, &program).with_flush_policy(flush_policy);
+        let mut write_destination = FlushRecorder {
+            written: vec![],
+            flushed_at: vec![],
+        };
+        let result = virtual_machine.interpret(&mut Cursor::new(input), &mut write_destination);
+
+        assert!(result.is_ok());
+        write_destination.flushed_at
+    }
+
+    /// Should flush after every single byte by default.
+    #[test]
+    fn flush_always() {
+        let flushed_at = flushed_at("+..", &[], FlushPolicy::default());
+        assert_eq!(flushed_at[..2], [1, 2]);
+    }
+
+    /// Should flush after newlines and before reading input.
+    #[test]
+    fn flush_on_newline() {
+        let flushed_at = flushed_at("+.+++++++++.>+.,.", b"a", FlushPolicy::Newline);
+        assert_eq!(flushed_at[..3], [2, 3, 4]);
+    }
+
+    /// Should flush only before reading input, and at exit.
+    #[test]
+    fn flush_before_input() {
+        let flushed_at = flushed_at("+..,..", b"a", FlushPolicy::BeforeInput);
+        assert_eq!(flushed_at[..2], [2, 4]);
+    }
+
+    /// Should flush only at exit, with the trailing newline.
+    #[test]
+    fn flush_at_exit() {
+        let flushed_at = flushed_at("+..,..", b"a", FlushPolicy::AtExit);
+        assert_eq!(flushed_at[0], 4);
+    }
+
     /// Should construct matching brackets.
     #[test]
     fn should_construct_matching_brackets() {
-        let program = Program::new("", "[]");
+        let program = Program::new("", "[]").validated().unwrap();
         let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
 
-        let mut expected_open_to_close: HashMap<usize, usize> = HashMap::new();
-        expected_open_to_close.insert(0, 1);
-        let mut expected_close_to_open: HashMap<usize, usize> = HashMap::new();
-        expected_close_to_open.insert(1, 0);
+        assert_eq!(virtual_machine.compiled().jump_table, vec![1, 0]);
+    }
+
+    /// Should construct nested matching brackets and map everything else to itself.
+    #[test]
+    fn should_construct_nested_matching_brackets() {
+        let program = Program::new("", "[.[.]]").validated().unwrap();
+        let virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
 
-        assert_eq!(virtual_machine.open_to_close, expected_open_to_close);
-        assert_eq!(virtual_machine.close_to_open, expected_close_to_open);
+        assert_eq!(
+            virtual_machine.compiled().jump_table,
+            vec![5, 1, 4, 3, 2, 0]
+        );
     }
 
     /// Should move program counter to the next instruction after end loop.
     #[test]
     fn should_move_program_counter_to_next_ins_after_end_loop() {
-        let program = Program::new("", "[]");
+        let program = Program::new("", "[]").validated().unwrap();
         let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
         let result = virtual_machine.interpret(&mut stdin(), &mut stdout());
 
@@ -455,10 +2256,11 @@ mod tests {
     /// Should move program counter to left.
     #[test]
     fn should_move_program_counter_to_left() {
-        let program = Program::new("", "+[]");
+        let program = Program::new("", "+[]").validated().unwrap();
         let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
-        virtual_machine.program_counter = virtual_machine.increment().unwrap();
-        virtual_machine.program_counter = virtual_machine.begin_loop().unwrap();
+        let jump_table = virtual_machine.compiled().jump_table.clone();
+        virtual_machine.program_counter = virtual_machine.add(0, 1, 0).unwrap();
+        virtual_machine.program_counter = virtual_machine.begin_loop(&jump_table).unwrap();
 
         assert_eq!(virtual_machine.program_counter, 2);
     }
@@ -466,11 +2268,12 @@ mod tests {
     /// Should move program counter back to loop start plus 1.
     #[test]
     fn should_move_program_counter_back_to_loop_start_plus_1() {
-        let program = Program::new("", "+[]");
+        let program = Program::new("", "+[]").validated().unwrap();
         let mut virtual_machine: VM<u8> = VM::new(NonZeroUsize::new(2).unwrap(), false, &program);
-        virtual_machine.program_counter = virtual_machine.increment().unwrap();
-        virtual_machine.program_counter = virtual_machine.begin_loop().unwrap();
-        virtual_machine.program_counter = virtual_machine.end_loop().unwrap();
+        let jump_table = virtual_machine.compiled().jump_table.clone();
+        virtual_machine.program_counter = virtual_machine.add(0, 1, 0).unwrap();
+        virtual_machine.program_counter = virtual_machine.begin_loop(&jump_table).unwrap();
+        virtual_machine.program_counter = virtual_machine.end_loop(&jump_table).unwrap();
 
         assert_eq!(virtual_machine.program_counter, 2);
     }
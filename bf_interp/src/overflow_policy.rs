@@ -0,0 +1,31 @@
+//! What the [VM](crate::VM) does when a Brainfuck program increments a cell beyond its max value, or decrements it
+//! below its min value.
+
+use bf_types::impl_named;
+
+/// How a [CellKind](crate::cell_kind::CellKind) cell behaves at its bounds.
+/// # Example
+/// ```
+/// # use bf_interp::overflow_policy::OverflowPolicy;
+/// assert_eq!("saturate".parse::<OverflowPolicy>(), Ok(OverflowPolicy::Saturating));
+/// assert!("panic".parse::<OverflowPolicy>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    /// Wrap around, e.g., incrementing a [u8] cell holding 255 gives 0.
+    #[default]
+    Wrapping,
+
+    /// Stay at the bound, e.g., incrementing a [u8] cell holding 255 gives 255.
+    Saturating,
+
+    /// Fail with a [CellOverflowError](crate::brainfuck_runtime_error::BrainfuckRuntimeError::CellOverflowError),
+    /// leaving the cell at the bound.
+    Error,
+}
+
+impl_named!(OverflowPolicy, "overflow policy", {
+    Wrapping => "wrap",
+    Saturating => "saturate",
+    Error => "error",
+});
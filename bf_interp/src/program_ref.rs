@@ -0,0 +1,27 @@
+//! How a [VM](crate::VM) holds the [ValidatedProgram] it interprets.
+
+use bf_types::ValidatedProgram;
+use std::ops::Deref;
+use std::sync::Arc;
+
+/// A [ValidatedProgram] which is either borrowed, or shared with anyone else holding it, so that a [VM](crate::VM) holding it
+/// does not have to outlive anything.
+#[derive(Debug, Clone)]
+pub enum ProgramRef<'a> {
+    /// See [VM::new](crate::VM::new).
+    Borrowed(&'a ValidatedProgram),
+
+    /// See [VM::with_shared_program](crate::VM::with_shared_program).
+    Shared(Arc<ValidatedProgram>),
+}
+
+impl Deref for ProgramRef<'_> {
+    type Target = ValidatedProgram;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            ProgramRef::Borrowed(program) => program,
+            ProgramRef::Shared(program) => program,
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! What state a [VM](crate::VM) was in when it ran into an error, see
+//! [VM::with_state_context](crate::VM::with_state_context).
+
+use std::fmt;
+
+/// The pointer, program counter, step count and cells around the pointer of a [VM](crate::VM) when it ran into an
+/// error. Its [fmt::Display] tells where the pointer was, the op it was at and how many steps it ran.
+/// # Example
+/// ```
+/// # use bf_interp::state_context::StateContext;
+/// let state = StateContext::new(2, 1, 1, 0, vec![0, 0, 7]);
+/// assert_eq!(state.to_string(), "pointer at 2, at op 1 after 1 step");
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateContext {
+    pointer: isize,
+    program_counter: usize,
+    steps: usize,
+
+    /// Index of the first cell of `cells`.
+    first_index: isize,
+
+    /// Cells around the pointer, as many as there are on each side of it up to the window the
+    /// [VM](crate::VM) was given.
+    cells: Vec<i128>,
+}
+
+impl StateContext {
+    /// Creates a [StateContext] in which `cells` start at index `first_index`.
+    pub fn new(
+        pointer: isize,
+        program_counter: usize,
+        steps: usize,
+        first_index: isize,
+        cells: Vec<i128>,
+    ) -> Self {
+        Self {
+            pointer,
+            program_counter,
+            steps,
+            first_index,
+            cells,
+        }
+    }
+
+    /// Getter.
+    pub fn pointer(&self) -> isize {
+        self.pointer
+    }
+
+    /// Index of the [Op](bf_types::ir::Op) the [VM](crate::VM) was at in its [Ir](bf_types::ir::Ir), which depends on
+    /// the passes it ran, see [VM::program_counter](crate::VM::program_counter).
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    /// Number of steps run, see [VM::steps](crate::VM::steps).
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    /// Getter.
+    pub fn first_index(&self) -> isize {
+        self.first_index
+    }
+
+    /// Getter.
+    pub fn cells(&self) -> &[i128] {
+        &self.cells
+    }
+
+    /// The cells around the pointer, with the one at the pointer in brackets.
+    /// # Example
+    /// ```
+    /// # use bf_interp::state_context::StateContext;
+    /// let state = StateContext::new(1, 4, 9, 0, vec![3, 0, 255]);
+    /// assert_eq!(state.tape_window(), "cells 0..=2: 3 [0] 255");
+    /// ```
+    pub fn tape_window(&self) -> String {
+        let last_index = self.first_index + self.cells.len() as isize - 1;
+        let cells: Vec<String> = self
+            .cells
+            .iter()
+            .zip(self.first_index..)
+            .map(|(cell, index)| {
+                if index == self.pointer {
+                    format!("[{}]", cell)
+                } else {
+                    cell.to_string()
+                }
+            })
+            .collect();
+        format!(
+            "cells {}..={}: {}",
+            self.first_index,
+            last_index,
+            cells.join(" ")
+        )
+    }
+}
+
+impl fmt::Display for StateContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pointer at {}, at op {} after {} {}",
+            self.pointer,
+            self.program_counter,
+            self.steps,
+            if self.steps == 1 { "step" } else { "steps" }
+        )
+    }
+}
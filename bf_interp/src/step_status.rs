@@ -0,0 +1,29 @@
+//! Where a [VM](crate::VM) stands after running it one step at a time.
+
+use crate::brainfuck_runtime_error::BrainfuckRuntimeError;
+
+/// What became of the [VM](crate::VM) after [VM::step](crate::VM::step) or [VM::run_until](crate::VM::run_until).
+#[derive(Debug)]
+pub enum StepStatus {
+    /// The program has more steps to run.
+    Running,
+
+    /// The program has run to its end, there is nothing left to run.
+    Halted,
+
+    /// The next step reads input but the input source has none yet, i.e., it failed with
+    /// [ErrorKind::WouldBlock](std::io::ErrorKind::WouldBlock). Nothing was run, so the step can be tried again once
+    /// there is some input.
+    NeedsInput,
+
+    /// The step failed. The [VM](crate::VM) is left at the failed step, so running it again fails again unless
+    /// something changed, e.g., a cancelled [VM](crate::VM) stays cancelled.
+    Error(BrainfuckRuntimeError),
+}
+
+impl StepStatus {
+    /// If the program has more steps to run, and running them could change something.
+    pub fn is_running(&self) -> bool {
+        matches!(self, StepStatus::Running)
+    }
+}
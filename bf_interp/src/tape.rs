@@ -0,0 +1,621 @@
+//! The memory of a Brainfuck [VM](crate::VM), and how it behaves at its edges.
+
+use crate::cell_kind::CellKind;
+use bf_types::impl_named;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+use std::num::NonZeroUsize;
+use std::ops::{Index, IndexMut, RangeInclusive};
+use std::str::FromStr;
+
+/// What happens when the [VM](crate::VM) pointer would move beyond an edge of its [Tape].
+/// # Example
+/// ```
+/// # use bf_interp::tape::Topology;
+/// assert_eq!("bidirectional".parse::<Topology>(), Ok(Topology::Bidirectional));
+/// assert!("infinite".parse::<Topology>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Topology {
+    /// Moving beyond either edge is an error.
+    #[default]
+    Bounded,
+
+    /// The tape grows to the right when needed, moving beyond its left edge is an error.
+    Extensible,
+
+    /// The tape grows to the left and to the right when needed, so cells with negative indices can be used.
+    Bidirectional,
+
+    /// The tape never grows, moving beyond either edge wraps around to the other edge.
+    Circular,
+}
+
+/// How a [Tape] stores its cells.
+/// # Example
+/// ```
+/// # use bf_interp::tape::Backend;
+/// assert_eq!("sparse".parse::<Backend>(), Ok(Backend::Sparse));
+/// assert!("compressed".parse::<Backend>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Every cell is allocated, the fastest one.
+    #[default]
+    Dense,
+
+    /// Cells are allocated by pages of [PAGE_SIZE] cells, and a page is only allocated once one of its cells is
+    /// written to. So a program may walk far away without allocating every cell it went through. Setting a cell of a
+    /// missing page to zero with [Tape::set] does not allocate it, but any other write does, even one leaving the cell
+    /// at zero.
+    Sparse,
+}
+
+/// How much a [Tape] grows once the pointer moves beyond one of its edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GrowthPolicy {
+    /// Double the number of cells, so that growing is cheap on average.
+    #[default]
+    Doubling,
+
+    /// Add this many cells.
+    Increment(NonZeroUsize),
+
+    /// Add just the cells needed to reach the pointer, so that no memory is wasted.
+    Exact,
+}
+
+/// Number of cells in a page of a [Backend::Sparse] tape.
+pub const PAGE_SIZE: usize = 4096;
+
+/// A [Tape] could not allocate memory, either because of its memory limit or because the allocator failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfMemory;
+
+/// The cells of a [VM](crate::VM). Cells are addressed by their logical index, where cell 0 is the one the pointer
+/// starts at, so growing the tape to the left gives cells with negative indices and never renumbers existing ones.
+#[derive(Debug, Clone)]
+pub struct Tape<T> {
+    storage: Storage<T>,
+    first_index: isize,
+    last_index: isize,
+    memory_limit: Option<usize>,
+    max_cells: Option<usize>,
+    growth_policy: GrowthPolicy,
+}
+
+/// The cells of a [Tape], as stored by its [Backend].
+#[derive(Debug, Clone)]
+enum Storage<T> {
+    /// The cell at index `i` is at position `i - first_index`.
+    Dense(Vec<T>),
+
+    /// The cell at index `i` is at position `i.rem_euclid(PAGE_SIZE)` of page `i.div_euclid(PAGE_SIZE)`. Cells of
+    /// missing pages are `zero`.
+    Sparse {
+        pages: HashMap<isize, Box<[T]>>,
+        zero: T,
+    },
+}
+
+impl_named!(Topology, "tape topology", {
+    Bounded => "bounded",
+    Extensible => "extensible",
+    Bidirectional => "bidirectional",
+    Circular => "circular",
+});
+
+impl Topology {
+    /// If the tape grows when the pointer moves beyond its left edge.
+    pub fn grows_left(&self) -> bool {
+        matches!(self, Topology::Bidirectional)
+    }
+
+    /// If the tape grows when the pointer moves beyond its right edge.
+    pub fn grows_right(&self) -> bool {
+        matches!(self, Topology::Extensible | Topology::Bidirectional)
+    }
+}
+
+impl_named!(Backend, "tape backend", {
+    Dense => "dense",
+    Sparse => "sparse",
+});
+
+impl GrowthPolicy {
+    /// Number of cells of a tape of `len` cells once it has grown by this policy, where `needed` is the smallest number
+    /// of cells it must have at the end.
+    pub fn grow(&self, len: usize, needed: usize) -> usize {
+        match self {
+            GrowthPolicy::Doubling => len.saturating_mul(2),
+            GrowthPolicy::Increment(increment) => len.saturating_add(increment.get()),
+            GrowthPolicy::Exact => needed,
+        }
+    }
+}
+
+impl fmt::Display for GrowthPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrowthPolicy::Doubling => write!(f, "doubling"),
+            GrowthPolicy::Increment(increment) => write!(f, "increment:{}", increment),
+            GrowthPolicy::Exact => write!(f, "exact"),
+        }
+    }
+}
+
+impl FromStr for GrowthPolicy {
+    type Err = String;
+
+    /// Parse a policy from `doubling`, `exact`, or `increment:<cells>`.
+    /// # Example
+    /// ```
+    /// # use bf_interp::tape::GrowthPolicy;
+    /// use std::num::NonZeroUsize;
+    /// assert_eq!("exact".parse::<GrowthPolicy>(), Ok(GrowthPolicy::Exact));
+    /// assert_eq!(
+    ///     "increment:1024".parse::<GrowthPolicy>(),
+    ///     Ok(GrowthPolicy::Increment(NonZeroUsize::new(1024).unwrap()))
+    /// );
+    /// assert!("increment:0".parse::<GrowthPolicy>().is_err());
+    /// assert!("tripling".parse::<GrowthPolicy>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "doubling" => Ok(GrowthPolicy::Doubling),
+            None if s == "exact" => Ok(GrowthPolicy::Exact),
+            Some(("increment", increment)) => {
+                increment.parse().map(GrowthPolicy::Increment).map_err(|_| {
+                    format!(
+                        "invalid growth increment '{}', expected a positive number",
+                        increment
+                    )
+                })
+            }
+            _ => Err(format!(
+                "unknown growth policy '{}', expected one of doubling, exact, increment:<cells>",
+                s
+            )),
+        }
+    }
+}
+
+impl<T> Tape<T>
+where
+    T: CellKind,
+{
+    /// Creates a [Backend::Dense] [Tape] of `len` zero cells, from index 0 to `len - 1`.
+    pub fn new(len: NonZeroUsize) -> Self {
+        Self::with_backend(len, Backend::Dense)
+    }
+
+    /// Creates a [Tape] of `len` zero cells, from index 0 to `len - 1`, stored by `backend`.
+    /// # Example
+    /// ```
+    /// # use bf_interp::tape::*;
+    /// use std::num::NonZeroUsize;
+    /// let mut tape: Tape<u8> = Tape::with_backend(NonZeroUsize::new(1_000_000).unwrap(), Backend::Sparse);
+    /// tape[999_999] = 7;
+    /// assert_eq!(tape[999_999], 7);
+    /// assert_eq!(tape.allocated_bytes(), PAGE_SIZE);
+    /// ```
+    pub fn with_backend(len: NonZeroUsize, backend: Backend) -> Self {
+        let storage = match backend {
+            Backend::Dense => Storage::Dense(vec![T::zero(); len.get()]),
+            Backend::Sparse => Storage::Sparse {
+                pages: HashMap::new(),
+                zero: T::zero(),
+            },
+        };
+        Self {
+            storage,
+            first_index: 0,
+            last_index: len.get() as isize - 1,
+            memory_limit: None,
+            max_cells: None,
+            growth_policy: GrowthPolicy::default(),
+        }
+    }
+
+    /// Limits how many bytes the cells may take up, the tape fails to grow or to allocate a page beyond that. The
+    /// cells a [Backend::Dense] tape is created with are always allowed.
+    pub fn with_memory_limit(mut self, memory_limit: Option<usize>) -> Self {
+        self.memory_limit = memory_limit;
+        self
+    }
+
+    /// Limits how many cells the tape may have, it fails to grow beyond that. The cells the tape is created with are
+    /// always allowed.
+    pub fn with_max_cells(mut self, max_cells: Option<usize>) -> Self {
+        self.max_cells = max_cells;
+        self
+    }
+
+    /// Sets how much the tape grows at once.
+    pub fn with_growth_policy(mut self, growth_policy: GrowthPolicy) -> Self {
+        self.growth_policy = growth_policy;
+        self
+    }
+
+    /// Makes the tape `len` zero cells from index 0 to `len - 1` again, like a new one with the same limits and growth
+    /// policy. A [Backend::Dense] tape keeps the memory it has allocated.
+    pub fn reset(&mut self, len: NonZeroUsize) {
+        match &mut self.storage {
+            Storage::Dense(cells) => {
+                cells.clear();
+                cells.resize(len.get(), T::zero());
+            }
+            Storage::Sparse { pages, .. } => pages.clear(),
+        }
+        (self.first_index, self.last_index) = (0, len.get() as isize - 1);
+    }
+
+    /// Number of cells.
+    pub fn len(&self) -> usize {
+        (self.last_index - self.first_index) as usize + 1
+    }
+
+    /// Always false, a [Tape] has at least one cell.
+    pub fn is_empty(&self) -> bool {
+        self.last_index < self.first_index
+    }
+
+    /// The index of the left most cell, which is negative once the tape has grown to the left.
+    pub fn first_index(&self) -> isize {
+        self.first_index
+    }
+
+    /// The index of the right most cell.
+    pub fn last_index(&self) -> isize {
+        self.last_index
+    }
+
+    /// If there is a cell at `index`.
+    pub fn contains(&self, index: isize) -> bool {
+        self.first_index <= index && index <= self.last_index
+    }
+
+    /// The cell at `index`, if any.
+    pub fn get(&self, index: isize) -> Option<&T> {
+        self.contains(index).then(|| &self[index])
+    }
+
+    /// Every cell from [Tape::first_index] to [Tape::last_index].
+    /// # Example
+    /// ```
+    /// # use bf_interp::tape::Tape;
+    /// use std::num::NonZeroUsize;
+    /// let mut tape: Tape<u8> = Tape::new(NonZeroUsize::new(3).unwrap());
+    /// tape[2] = 7;
+    /// assert!(tape.iter().eq(&[0, 0, 7]));
+    /// ```
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        (self.first_index..=self.last_index).map(|index| &self[index])
+    }
+
+    /// How many bytes the allocated cells take up.
+    pub fn allocated_bytes(&self) -> usize {
+        match &self.storage {
+            Storage::Dense(cells) => cells.len() * size_of::<T>(),
+            Storage::Sparse { pages, .. } => pages.len() * PAGE_SIZE * size_of::<T>(),
+        }
+    }
+
+    /// Getter.
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
+
+    /// Getter.
+    pub fn max_cells(&self) -> Option<usize> {
+        self.max_cells
+    }
+
+    /// Getter.
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.growth_policy
+    }
+
+    /// The cell at `index`, allocating its page first if needed. Panics if there is no cell at `index`.
+    #[inline]
+    pub fn get_mut(&mut self, index: isize) -> Result<&mut T, OutOfMemory> {
+        match &mut self.storage {
+            Storage::Dense(cells) => Ok(&mut cells[(index - self.first_index) as usize]),
+            Storage::Sparse { pages, .. } => sparse_index_mut(
+                pages,
+                index,
+                self.first_index..=self.last_index,
+                self.memory_limit,
+            ),
+        }
+    }
+
+    /// Sets the cell at `index` to `value`. Setting a cell of a missing page to zero does not allocate the page.
+    pub fn set(&mut self, index: isize, value: T) -> Result<(), OutOfMemory> {
+        if let Storage::Sparse { pages, .. } = &self.storage {
+            if value == T::zero() && !pages.contains_key(&index.div_euclid(PAGE_SIZE as isize)) {
+                return Ok(());
+            }
+        }
+        *self.get_mut(index)? = value;
+        Ok(())
+    }
+
+    /// The index of the cell `index` lands on when moving around the tape as if both edges were joined together.
+    /// # Example
+    /// ```
+    /// # use bf_interp::tape::Tape;
+    /// use std::num::NonZeroUsize;
+    /// let tape: Tape<u8> = Tape::new(NonZeroUsize::new(3).unwrap());
+    /// assert_eq!(tape.wrap(-1), 2);
+    /// assert_eq!(tape.wrap(7), 1);
+    /// ```
+    pub fn wrap(&self, index: isize) -> isize {
+        self.first_index + (index - self.first_index).rem_euclid(self.len() as isize)
+    }
+
+    /// The most cells the tape may have. A [Backend::Dense] tape is also bounded by its memory limit, while a
+    /// [Backend::Sparse] one does not allocate anything for growing.
+    fn capacity(&self) -> usize {
+        let mut capacity = self
+            .max_cells
+            .unwrap_or(usize::MAX)
+            .min(isize::MAX as usize / 2);
+        if let (Storage::Dense(_), Some(limit)) = (&self.storage, self.memory_limit) {
+            capacity = capacity.min(limit / size_of::<T>().max(1));
+        }
+        capacity
+    }
+
+    /// Number of cells a tape of `len` cells has once it has grown by its [GrowthPolicy] to at least `needed` cells.
+    /// If its capacity is reached first, gives the error along with the capacity, as long as it is more than `len`.
+    fn grown_len(&self, mut len: usize, needed: usize) -> (usize, Result<(), OutOfMemory>) {
+        let capacity = self.capacity();
+        while len < needed {
+            let grown = self.growth_policy.grow(len, needed).min(capacity);
+            if grown <= len {
+                return (len, Err(OutOfMemory));
+            }
+            len = grown;
+        }
+        (len, Ok(()))
+    }
+
+    /// Grow the tape to the right by its [GrowthPolicy] until there is a cell at `index`. If a limit is reached first,
+    /// the tape is left at the most cells it may have.
+    pub(crate) fn grow_right(&mut self, index: isize) -> Result<(), OutOfMemory> {
+        let (len, result) = self.grown_len(self.len(), (index - self.first_index) as usize + 1);
+        self.resize(self.first_index, self.first_index + len as isize - 1)?;
+        result
+    }
+
+    /// Grow the tape to the left by its [GrowthPolicy] until there is a cell at `index`. If a limit is reached first,
+    /// the tape is left at the most cells it may have.
+    pub(crate) fn grow_left(&mut self, index: isize) -> Result<(), OutOfMemory> {
+        let (len, result) = self.grown_len(self.len(), (self.last_index - index) as usize + 1);
+        self.resize(self.last_index - len as isize + 1, self.last_index)?;
+        result
+    }
+
+    /// Grow the tape the same way as visiting every index of `path` in order would, so that it ends up just as large
+    /// whichever edge is reached first. If a limit would be reached on the way, the tape is left untouched.
+    pub(crate) fn grow_along(
+        &mut self,
+        path: impl IntoIterator<Item = isize>,
+    ) -> Result<(), OutOfMemory> {
+        let (mut first, mut last) = (self.first_index, self.last_index);
+        for index in path {
+            let len = (last - first) as usize + 1;
+            if index > last {
+                let (len, result) = self.grown_len(len, (index - first) as usize + 1);
+                result?;
+                last = first + len as isize - 1;
+            } else if index < first {
+                let (len, result) = self.grown_len(len, (last - index) as usize + 1);
+                result?;
+                first = last - len as isize + 1;
+            }
+        }
+        self.resize(first, last)
+    }
+
+    /// Add zero cells so that the tape goes from `first` to `last`, which must not be within the current edges.
+    fn resize(&mut self, first: isize, last: isize) -> Result<(), OutOfMemory> {
+        let (left, right) = (
+            (self.first_index - first) as usize,
+            (last - self.last_index) as usize,
+        );
+        if let Storage::Dense(cells) = &mut self.storage {
+            cells
+                .try_reserve_exact(left + right)
+                .map_err(|_| OutOfMemory)?;
+            cells.splice(0..0, std::iter::repeat_n(T::zero(), left));
+            cells.resize(cells.len() + right, T::zero());
+        }
+        (self.first_index, self.last_index) = (first, last);
+        Ok(())
+    }
+}
+
+impl<T> Index<isize> for Tape<T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: isize) -> &Self::Output {
+        match &self.storage {
+            Storage::Dense(cells) => &cells[(index - self.first_index) as usize],
+            Storage::Sparse { pages, zero } => {
+                sparse_index(pages, zero, index, self.first_index..=self.last_index)
+            }
+        }
+    }
+}
+
+/// [Tape::get_mut] for a [Backend::Sparse] tape holding cells in `range`, kept out of line so that the
+/// [Backend::Dense] one stays small.
+#[inline(never)]
+fn sparse_index_mut<T>(
+    pages: &mut HashMap<isize, Box<[T]>>,
+    index: isize,
+    range: RangeInclusive<isize>,
+    memory_limit: Option<usize>,
+) -> Result<&mut T, OutOfMemory>
+where
+    T: CellKind,
+{
+    assert!(range.contains(&index), "no cell at index {index}");
+    let page_bytes = PAGE_SIZE * size_of::<T>();
+    let page_index = index.div_euclid(PAGE_SIZE as isize);
+    if !pages.contains_key(&page_index) {
+        let allocated_bytes = pages.len() * page_bytes;
+        if memory_limit.is_some_and(|limit| allocated_bytes + page_bytes > limit) {
+            return Err(OutOfMemory);
+        }
+        let mut page = Vec::new();
+        page.try_reserve_exact(PAGE_SIZE).map_err(|_| OutOfMemory)?;
+        page.resize(PAGE_SIZE, T::zero());
+        pages.insert(page_index, page.into_boxed_slice());
+    }
+    let page = pages
+        .get_mut(&page_index)
+        .expect("the page was just allocated");
+    Ok(&mut page[index.rem_euclid(PAGE_SIZE as isize) as usize])
+}
+
+/// [Index::index] for a [Backend::Sparse] tape holding cells in `range`, kept out of line so that the
+/// [Backend::Dense] one stays small.
+#[inline(never)]
+fn sparse_index<'a, T>(
+    pages: &'a HashMap<isize, Box<[T]>>,
+    zero: &'a T,
+    index: isize,
+    range: RangeInclusive<isize>,
+) -> &'a T {
+    assert!(range.contains(&index), "no cell at index {index}");
+    pages
+        .get(&index.div_euclid(PAGE_SIZE as isize))
+        .map_or(zero, |page| {
+            &page[index.rem_euclid(PAGE_SIZE as isize) as usize]
+        })
+}
+
+impl<T> IndexMut<isize> for Tape<T>
+where
+    T: CellKind,
+{
+    /// Panics if the page of the cell cannot be allocated, use [Tape::get_mut] to handle it.
+    #[inline]
+    fn index_mut(&mut self, index: isize) -> &mut Self::Output {
+        self.get_mut(index)
+            .expect("cannot allocate the page of a cell within the memory limit")
+    }
+}
+
+impl<T> PartialEq for Tape<T>
+where
+    T: CellKind,
+{
+    /// Tapes are equal if they have the same cells at the same indices, no matter their [Backend].
+    fn eq(&self, other: &Self) -> bool {
+        self.first_index == other.first_index && self.iter().eq(other.iter())
+    }
+}
+
+impl<T, const N: usize> PartialEq<[T; N]> for Tape<T>
+where
+    T: CellKind,
+{
+    fn eq(&self, other: &[T; N]) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Should grow to the right without renumbering cells.
+    #[test]
+    fn grow_right() {
+        let mut tape: Tape<u8> = Tape::new(NonZeroUsize::new(2).unwrap());
+        tape[1] = 1;
+        tape.grow_right(4).unwrap();
+
+        assert_eq!(tape.len(), 8);
+        assert_eq!((tape.first_index(), tape.last_index()), (0, 7));
+        assert_eq!(tape[1], 1);
+    }
+
+    /// Should grow to the left without renumbering cells.
+    #[test]
+    fn grow_left() {
+        let mut tape: Tape<u8> = Tape::new(NonZeroUsize::new(2).unwrap());
+        tape[0] = 1;
+        tape.grow_left(-1).unwrap();
+        tape[-1] = 2;
+
+        assert_eq!(tape, [0, 2, 1, 0]);
+        assert_eq!((tape.first_index(), tape.last_index()), (-2, 1));
+        assert_eq!(tape.get(-2), Some(&0));
+        assert_eq!(tape.get(-3), None);
+
+        tape.grow_left(-5).unwrap();
+        assert_eq!(tape.len(), 8);
+        assert_eq!(tape[-1], 2);
+        assert_eq!(tape[0], 1);
+    }
+
+    /// Should only allocate pages written to, and compare equal to a dense tape with the same cells.
+    #[test]
+    fn allocate_sparse_pages() {
+        let len = NonZeroUsize::new(3 * PAGE_SIZE).unwrap();
+        let mut sparse: Tape<u16> = Tape::with_backend(len, Backend::Sparse);
+        let mut dense: Tape<u16> = Tape::new(len);
+        sparse.set(0, 0).unwrap();
+        sparse.grow_left(-1).unwrap();
+        dense.grow_left(-1).unwrap();
+        sparse[-1] = 1;
+        dense[-1] = 1;
+
+        assert_eq!(sparse.allocated_bytes(), PAGE_SIZE * 2);
+        assert_eq!(sparse[2 * PAGE_SIZE as isize], 0);
+        assert_eq!(sparse, dense);
+    }
+
+    /// Should not grow or allocate beyond the memory limit.
+    #[test]
+    fn respect_memory_limit() {
+        let mut dense: Tape<u8> =
+            Tape::new(NonZeroUsize::new(2).unwrap()).with_memory_limit(Some(5));
+        assert_eq!(dense.grow_right(6), Err(OutOfMemory));
+        assert_eq!(dense.len(), 5);
+
+        let len = NonZeroUsize::new(2 * PAGE_SIZE).unwrap();
+        let mut sparse: Tape<u8> =
+            Tape::with_backend(len, Backend::Sparse).with_memory_limit(Some(PAGE_SIZE));
+        *sparse.get_mut(0).unwrap() = 1;
+        assert_eq!(sparse.get_mut(PAGE_SIZE as isize), Err(OutOfMemory));
+        assert_eq!(sparse.set(PAGE_SIZE as isize, 0), Ok(()));
+    }
+
+    /// Should grow by the growth policy, up to the max number of cells.
+    #[test]
+    fn grow_by_policy() {
+        let mut exact: Tape<u8> =
+            Tape::new(NonZeroUsize::new(2).unwrap()).with_growth_policy(GrowthPolicy::Exact);
+        exact.grow_right(4).unwrap();
+        exact.grow_left(-1).unwrap();
+        assert_eq!((exact.first_index(), exact.last_index()), (-1, 4));
+
+        let mut increment: Tape<u8> = Tape::new(NonZeroUsize::new(2).unwrap())
+            .with_growth_policy(GrowthPolicy::Increment(NonZeroUsize::new(3).unwrap()))
+            .with_max_cells(Some(7));
+        increment.grow_right(3).unwrap();
+        assert_eq!(increment.len(), 5);
+        assert_eq!(increment.grow_right(7), Err(OutOfMemory));
+        assert_eq!(increment.len(), 7);
+        assert_eq!(increment.grow_left(-1), Err(OutOfMemory));
+        assert_eq!(increment.first_index(), 0);
+    }
+}
@@ -0,0 +1,204 @@
+//! A machine readable description of an error, for tools which annotate source code with them. With the `serde`
+//! feature, a [Diagnostic] can be serialized, e.g., to JSON.
+//!
+//! Every kind of error has a stable code:
+//!
+//! | Code | Error |
+//! | ----- | ----- |
+//! | E0001 | A close bracket has no matching open bracket. |
+//! | E0002 | An open bracket has no matching close bracket. |
+//! | E0003 | The source file cannot be read. |
+//! | E0101 | The pointer cannot move left. |
+//! | E0102 | The pointer cannot move right. |
+//! | E0103 | Input cannot be read. |
+//! | E0104 | Output cannot be written. |
+//! | E0105 | A cell overflows and cannot wrap or saturate. |
+//! | E0106 | The memory would grow beyond its limit. |
+//! | E0107 | The program ran out of steps. |
+//! | E0108 | The program was cancelled. |
+
+use crate::{Instruction, Program};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::fmt;
+use std::path::Path;
+
+/// How bad a [Diagnostic] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "lowercase"))]
+#[non_exhaustive]
+pub enum Severity {
+    /// The program cannot run, or stopped running.
+    Error,
+}
+
+/// An error located in the source code of a [Program], see the [module documentation](self) for its codes.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Diagnostic {
+    code: &'static str,
+    severity: Severity,
+
+    /// Path of the source file, with any invalid UTF-8 replaced so that it can always be serialized.
+    file: String,
+
+    /// Row of the instruction at fault, counting from 1. Missing for errors which are not about any instruction.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    row: Option<usize>,
+
+    /// Column of the instruction at fault, counting characters from 1.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    col: Option<usize>,
+
+    /// Offset of the instruction at fault from the start of the source code, in bytes.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    byte_offset: Option<usize>,
+
+    /// What went wrong, without where it did since the fields above tell that.
+    message: String,
+
+    /// Where the pointer was, for errors at run time.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pointer: Option<isize>,
+
+    /// Value of the cell the pointer was at, for errors at run time.
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    cell: Option<i128>,
+}
+
+impl Diagnostic {
+    /// Creates an error [Diagnostic] about the file at `file_path`, which is not about any instruction yet.
+    /// # Example
+    /// ```
+    /// # use bf_types::*;
+    /// use bf_types::diagnostic::Diagnostic;
+    /// let program = Program::new("hello.bf", "+\n-]");
+    /// let diagnostic = Diagnostic::new("E0001", "unmatched bracket", program.file_path())
+    ///     .with_location(&program, program.instructions()[2]);
+    /// assert_eq!(diagnostic.byte_offset(), Some(3));
+    /// ```
+    pub fn new(code: &'static str, message: impl fmt::Display, file_path: &Path) -> Self {
+        Self {
+            code,
+            severity: Severity::Error,
+            file: file_path.to_string_lossy().into_owned(),
+            row: None,
+            col: None,
+            byte_offset: None,
+            message: message.to_string(),
+            pointer: None,
+            cell: None,
+        }
+    }
+
+    /// Sets which instruction of `program` is at fault.
+    pub fn with_location(mut self, program: &Program, instruction: Instruction) -> Self {
+        self.row = Some(instruction.row());
+        self.col = Some(instruction.col());
+        self.byte_offset = program.byte_offset(instruction);
+        self
+    }
+
+    /// Sets where the pointer was when the error happened.
+    pub fn with_pointer(mut self, pointer: isize) -> Self {
+        self.pointer = Some(pointer);
+        self
+    }
+
+    /// Sets the value of the cell the pointer was at when the error happened.
+    pub fn with_cell(mut self, cell: i128) -> Self {
+        self.cell = Some(cell);
+        self
+    }
+
+    /// Getter.
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    /// Getter.
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    /// Getter.
+    pub fn file(&self) -> &str {
+        &self.file
+    }
+
+    /// Getter.
+    pub fn row(&self) -> Option<usize> {
+        self.row
+    }
+
+    /// Getter.
+    pub fn col(&self) -> Option<usize> {
+        self.col
+    }
+
+    /// Getter.
+    pub fn byte_offset(&self) -> Option<usize> {
+        self.byte_offset
+    }
+
+    /// Getter.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Getter.
+    pub fn pointer(&self) -> Option<isize> {
+        self.pointer
+    }
+
+    /// Getter.
+    pub fn cell(&self) -> Option<i128> {
+        self.cell
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IncompatibleBracket;
+
+    /// Should locate an incompatible bracket by row, column and byte offset.
+    #[test]
+    fn locate_incompatible_bracket() {
+        let program = Program::new("f.bf", "中文+\n[-]\n-[");
+        let errors: Vec<IncompatibleBracket> =
+            program.validate().unwrap_err().into_iter().collect();
+        let diagnostic = errors[0].diagnostic(&program);
+
+        assert_eq!(diagnostic.code(), "E0002");
+        assert_eq!(diagnostic.severity(), Severity::Error);
+        assert_eq!(diagnostic.file(), "f.bf");
+        assert_eq!(diagnostic.row(), Some(3));
+        assert_eq!(diagnostic.col(), Some(2));
+        assert_eq!(diagnostic.byte_offset(), Some(13));
+        assert_eq!(diagnostic.message(), "unmatched '['");
+        assert_eq!(diagnostic.pointer(), None);
+    }
+
+    /// Should serialize to JSON, leaving out what is missing.
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_to_json() {
+        let program = Program::new("f.bf", "<");
+        let diagnostic = Diagnostic::new("E0101", "cannot move left", program.file_path())
+            .with_location(&program, program.instructions()[0])
+            .with_pointer(0)
+            .with_cell(-1);
+
+        assert_eq!(
+            serde_json::to_string(&diagnostic).unwrap(),
+            r#"{"code":"E0101","severity":"error","file":"f.bf","row":1,"col":1,"byte_offset":0,"message":"cannot move left","pointer":0,"cell":-1}"#
+        );
+
+        let diagnostic = Diagnostic::new("E0003", "No such file", Path::new("f.bf"));
+        assert_eq!(
+            serde_json::to_string(&diagnostic).unwrap(),
+            r#"{"code":"E0003","severity":"error","file":"f.bf","message":"No such file"}"#
+        );
+    }
+}
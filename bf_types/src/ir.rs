@@ -0,0 +1,727 @@
+//! An intermediate representation of a Brainfuck [Program], which can be optimized before being interpreted.
+
+use crate::{Program, RawInstruction};
+use std::collections::BTreeMap;
+
+/// A half-open range of indices into [Program::instructions], telling which source instructions an [Op] was built
+/// from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    start: usize,
+    end: usize,
+}
+
+/// A representation of an operation the VM can execute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Add `amount` to the cell at `offset` relative to VM pointer, a negative `amount` decrements it.
+    Add { offset: isize, amount: isize },
+
+    /// Move VM pointer by a number of cells, a negative number moves it to the left.
+    Move(isize),
+
+    /// Take value from user input.
+    Input,
+
+    /// Output the value pointed by VM pointer as ASCII.
+    Output,
+
+    /// Loop starts here.
+    BeginLoop,
+
+    /// Loop ends here.
+    EndLoop,
+
+    /// Set the cell pointed by VM pointer to zero, built from loops like `[-]` and `[+]`.
+    SetZero,
+
+    /// Keep moving VM pointer by a number of cells until it points to a zero cell, built from loops like `[>]` and
+    /// `[<<]`.
+    Scan(isize),
+
+    /// Add the value pointed by VM pointer multiplied by `factor` to the cell at `offset` relative to VM pointer,
+    /// built from loops like `[->+>++<<]`. The pointed cell itself is left unchanged.
+    MulAdd { offset: isize, factor: isize },
+
+    /// Make sure cells from VM pointer plus `min` to VM pointer plus `max` are accessible before running the [Op]s
+    /// built from the same [Span], as these [Op]s no longer move VM pointer step by step. If the [Span] is a loop, this
+    /// only applies when the pointed cell is not zero, otherwise the loop would be skipped.
+    Guard { min: isize, max: isize },
+}
+
+/// An [Op] together with the [Span] of source instructions it was built from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpannedOp {
+    op: Op,
+    span: Span,
+}
+
+/// The intermediate representation of a whole Brainfuck [Program].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ir {
+    ops: Vec<SpannedOp>,
+}
+
+impl Span {
+    /// Build a new instance of [Span] covering instructions from `start` (inclusive) to `end` (exclusive).
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Getter.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Getter.
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    /// Number of source instructions covered.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// If no source instruction is covered.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl Op {
+    /// Convert a [RawInstruction] to the equivalent single [Op].
+    /// # Example
+    /// ```
+    /// # use bf_types::RawInstruction;
+    /// # use bf_types::ir::Op;
+    /// assert_eq!(Op::from_raw(RawInstruction::MoveLeft), Op::Move(-1));
+    /// assert_eq!(
+    ///     Op::from_raw(RawInstruction::Increment),
+    ///     Op::Add { offset: 0, amount: 1 }
+    /// );
+    /// ```
+    pub fn from_raw(raw_instruction: RawInstruction) -> Self {
+        match raw_instruction {
+            RawInstruction::MoveLeft => Op::Move(-1),
+            RawInstruction::MoveRight => Op::Move(1),
+            RawInstruction::Increment => Op::Add {
+                offset: 0,
+                amount: 1,
+            },
+            RawInstruction::Decrement => Op::Add {
+                offset: 0,
+                amount: -1,
+            },
+            RawInstruction::Input => Op::Input,
+            RawInstruction::Output => Op::Output,
+            RawInstruction::BeginLoop => Op::BeginLoop,
+            RawInstruction::EndLoop => Op::EndLoop,
+        }
+    }
+}
+
+impl SpannedOp {
+    /// Build a new instance of [SpannedOp].
+    pub fn new(op: Op, span: Span) -> Self {
+        Self { op, span }
+    }
+
+    /// Getter.
+    pub fn op(&self) -> Op {
+        self.op
+    }
+
+    /// Getter.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl Ir {
+    /// Lowers a [Program] into its [Ir] without any optimization, i.e., one [Op] per source instruction.
+    /// # Example
+    /// ```
+    /// # use bf_types::Program;
+    /// # use bf_types::ir::*;
+    /// let program = Program::new("", "++>");
+    /// let ir = Ir::new(&program);
+    /// assert_eq!(ir.ops().len(), 3);
+    /// ```
+    pub fn new(program: &Program) -> Self {
+        let ops = program
+            .instructions()
+            .iter()
+            .enumerate()
+            .map(|(idx, ins)| {
+                SpannedOp::new(Op::from_raw(ins.raw_instruction()), Span::new(idx, idx + 1))
+            })
+            .collect();
+        Self { ops }
+    }
+
+    /// Folds runs of the same instruction into a single [Op], e.g., `+++++` into an [Op::Add] of 5 and `>>>` into
+    /// `Move(3)`.
+    /// Only runs of exactly the same instruction are folded, so a VM can still tell which source instruction in a run
+    /// caused an error.
+    /// # Example
+    /// ```
+    /// # use bf_types::Program;
+    /// # use bf_types::ir::*;
+    /// let program = Program::new("", "+++++>>>");
+    /// let ir = Ir::new(&program).fold();
+    /// assert_eq!(ir.ops()[0].op(), Op::Add { offset: 0, amount: 5 });
+    /// assert_eq!(ir.ops()[1].op(), Op::Move(3));
+    /// ```
+    pub fn fold(self) -> Self {
+        let mut ops: Vec<SpannedOp> = Vec::with_capacity(self.ops.len());
+        for spanned_op in self.ops {
+            if let Some(last) = ops.last_mut() {
+                if last.span.end == spanned_op.span.start {
+                    let folded = match (last.op, spanned_op.op) {
+                        (
+                            Op::Add { offset, amount: a },
+                            Op::Add {
+                                offset: other_offset,
+                                amount: b,
+                            },
+                        ) if offset == other_offset && a.signum() == b.signum() => Some(Op::Add {
+                            offset,
+                            amount: a + b,
+                        }),
+                        (Op::Move(a), Op::Move(b)) if a.signum() == b.signum() => {
+                            Some(Op::Move(a + b))
+                        }
+                        _ => None,
+                    };
+                    if let Some(op) = folded {
+                        last.op = op;
+                        last.span.end = spanned_op.span.end;
+                        continue;
+                    }
+                }
+            }
+            ops.push(spanned_op);
+        }
+        Self { ops }
+    }
+
+    /// Replaces loops like `[-]` and `[+]` with [Op::SetZero].
+    /// # Example
+    /// ```
+    /// # use bf_types::Program;
+    /// # use bf_types::ir::*;
+    /// let program = Program::new("", "[-]");
+    /// let ir = Ir::new(&program).clear_loops();
+    /// assert_eq!(ir.ops()[0].op(), Op::SetZero);
+    /// ```
+    pub fn clear_loops(self) -> Self {
+        self.rewrite_loops(|body, span| {
+            let mut total = 0;
+            for spanned_op in body {
+                match spanned_op.op {
+                    Op::Add { offset: 0, amount } => total += amount,
+                    _ => return None,
+                }
+            }
+            (total.abs() == 1).then(|| vec![SpannedOp::new(Op::SetZero, span)])
+        })
+    }
+
+    /// Replaces loops like `[>]` and `[<<]` with [Op::Scan].
+    /// # Example
+    /// ```
+    /// # use bf_types::Program;
+    /// # use bf_types::ir::*;
+    /// let program = Program::new("", "[<<]");
+    /// let ir = Ir::new(&program).scans();
+    /// assert_eq!(ir.ops()[0].op(), Op::Scan(-2));
+    /// ```
+    pub fn scans(self) -> Self {
+        self.rewrite_loops(|body, span| {
+            let mut total: isize = 0;
+            for spanned_op in body {
+                match spanned_op.op {
+                    Op::Move(distance) if total.signum() * distance.signum() >= 0 => {
+                        total += distance
+                    }
+                    _ => return None,
+                }
+            }
+            (total != 0).then(|| vec![SpannedOp::new(Op::Scan(total), span)])
+        })
+    }
+
+    /// Replaces balanced loops which only add to cells and change the pointed cell by exactly one per iteration, like
+    /// `[->+>++<<]`, with an [Op::Guard], one [Op::MulAdd] per changed cell and a final [Op::SetZero].
+    /// # Example
+    /// ```
+    /// # use bf_types::Program;
+    /// # use bf_types::ir::*;
+    /// let program = Program::new("", "[->+>++<<]");
+    /// let ir = Ir::new(&program).fold().multiply_loops();
+    /// let ops: Vec<Op> = ir.ops().iter().map(|spanned_op| spanned_op.op()).collect();
+    /// assert_eq!(
+    ///     ops,
+    ///     vec![
+    ///         Op::Guard { min: 0, max: 2 },
+    ///         Op::MulAdd { offset: 1, factor: 1 },
+    ///         Op::MulAdd { offset: 2, factor: 2 },
+    ///         Op::SetZero,
+    ///     ]
+    /// );
+    /// ```
+    pub fn multiply_loops(self) -> Self {
+        self.rewrite_loops(|body, span| {
+            let mut position: isize = 0;
+            let (mut min, mut max): (isize, isize) = (0, 0);
+            let mut amounts: BTreeMap<isize, isize> = BTreeMap::new();
+            for spanned_op in body {
+                match spanned_op.op {
+                    Op::Add { offset, amount } => {
+                        *amounts.entry(position + offset).or_insert(0) += amount
+                    }
+                    Op::Move(distance) => {
+                        position += distance;
+                        min = min.min(position);
+                        max = max.max(position);
+                    }
+                    _ => return None,
+                }
+            }
+
+            // The pointed cell must reach zero after exactly as many iterations as its initial value (or its
+            // negation), and the loop must end where it starts.
+            let step = amounts.remove(&0).unwrap_or(0);
+            if position != 0 || step.abs() != 1 {
+                return None;
+            }
+
+            let mut ops = vec![SpannedOp::new(Op::Guard { min, max }, span)];
+            for (offset, amount) in amounts.into_iter().filter(|(_, amount)| *amount != 0) {
+                let factor = -amount * step;
+                ops.push(SpannedOp::new(Op::MulAdd { offset, factor }, span));
+            }
+            ops.push(SpannedOp::new(Op::SetZero, span));
+            Some(ops)
+        })
+    }
+
+    /// Replaces pointer moves in each basic block, i.e., a run of [Op::Add]s and [Op::Move]s, with offsets on its
+    /// [Op::Add]s, so that VM pointer is moved only once at the end of the block. The block is preceded by an
+    /// [Op::Guard] covering every cell its moves went through.
+    /// # Example
+    /// ```
+    /// # use bf_types::Program;
+    /// # use bf_types::ir::*;
+    /// let program = Program::new("", ">+>+<<");
+    /// let ir = Ir::new(&program).fold().offsets();
+    /// let ops: Vec<Op> = ir.ops().iter().map(|spanned_op| spanned_op.op()).collect();
+    /// assert_eq!(
+    ///     ops,
+    ///     vec![
+    ///         Op::Guard { min: 0, max: 2 },
+    ///         Op::Add { offset: 1, amount: 1 },
+    ///         Op::Add { offset: 2, amount: 1 },
+    ///     ]
+    /// );
+    /// ```
+    pub fn offsets(self) -> Self {
+        let mut ops: Vec<SpannedOp> = Vec::with_capacity(self.ops.len());
+        let mut block: Vec<SpannedOp> = Vec::new();
+        for spanned_op in self.ops {
+            if matches!(spanned_op.op, Op::Add { .. } | Op::Move(_)) {
+                block.push(spanned_op);
+            } else {
+                Self::push_offset_block(&mut ops, &block);
+                block.clear();
+                ops.push(spanned_op);
+            }
+        }
+        Self::push_offset_block(&mut ops, &block);
+        Self { ops }
+    }
+
+    /// Pushes a basic block to `ops`, with its pointer moves replaced by offsets if it has more than one move.
+    fn push_offset_block(ops: &mut Vec<SpannedOp>, block: &[SpannedOp]) {
+        let moves = block
+            .iter()
+            .filter(|spanned_op| matches!(spanned_op.op, Op::Move(_)))
+            .count();
+        if moves < 2 {
+            ops.extend_from_slice(block);
+            return;
+        }
+
+        let span = Span::new(block[0].span.start, block[block.len() - 1].span.end);
+        let mut position: isize = 0;
+        let (mut min, mut max): (isize, isize) = (0, 0);
+        let mut adds: Vec<SpannedOp> = Vec::with_capacity(block.len());
+        for spanned_op in block {
+            match spanned_op.op {
+                Op::Add { offset, amount } => {
+                    let op = Op::Add {
+                        offset: position + offset,
+                        amount,
+                    };
+                    adds.push(SpannedOp::new(op, spanned_op.span));
+                }
+                Op::Move(distance) => {
+                    position += distance;
+                    min = min.min(position);
+                    max = max.max(position);
+                }
+                _ => unreachable!("a basic block only has Op::Add and Op::Move"),
+            }
+        }
+
+        ops.push(SpannedOp::new(Op::Guard { min, max }, span));
+        ops.extend(adds);
+        if position != 0 {
+            ops.push(SpannedOp::new(Op::Move(position), span));
+        }
+    }
+
+    /// Removes loops which can never run, as the pointed cell is known to be zero when they start. That is the case at
+    /// the very beginning of the program, assuming VM memory starts with zero cells, and right after a loop ends.
+    /// # Example
+    /// ```
+    /// # use bf_types::Program;
+    /// # use bf_types::ir::*;
+    /// let program = Program::new("", "[comment, loop.]+[-][-]");
+    /// let ir = Ir::new(&program).dead_code();
+    /// assert_eq!(ir.ops().len(), 4);
+    /// ```
+    pub fn dead_code(self) -> Self {
+        let mut ops: Vec<SpannedOp> = Vec::with_capacity(self.ops.len());
+        let mut is_zero = true;
+        let mut idx = 0;
+        while idx < self.ops.len() {
+            let spanned_op = self.ops[idx];
+            match spanned_op.op {
+                Op::BeginLoop if is_zero => {
+                    let mut depth = 0;
+                    for (close_idx, spanned_op) in self.ops.iter().enumerate().skip(idx) {
+                        match spanned_op.op {
+                            Op::BeginLoop => depth += 1,
+                            Op::EndLoop => depth -= 1,
+                            _ => {}
+                        }
+                        if depth == 0 {
+                            idx = close_idx + 1;
+                            break;
+                        }
+                    }
+                    continue;
+                }
+                Op::SetZero | Op::Scan(_) if is_zero => {}
+                Op::Guard { .. } if is_zero && self.is_multiply_loop(idx) => {
+                    // A multiply loop is its guard followed by ops sharing the same span.
+                    idx += 1;
+                    while idx < self.ops.len() && self.ops[idx].span == spanned_op.span {
+                        idx += 1;
+                    }
+                    continue;
+                }
+                _ => {
+                    is_zero = match spanned_op.op {
+                        Op::EndLoop | Op::SetZero | Op::Scan(_) => true,
+                        Op::Add { offset, .. } => is_zero && offset != 0,
+                        Op::Output | Op::MulAdd { .. } | Op::Guard { .. } => is_zero,
+                        Op::Move(_) | Op::Input | Op::BeginLoop => false,
+                    };
+                    ops.push(spanned_op);
+                }
+            }
+            idx += 1;
+        }
+        Self { ops }
+    }
+
+    /// If the [Op::Guard] at `idx` starts a multiply loop, rather than a basic block with offsets.
+    fn is_multiply_loop(&self, idx: usize) -> bool {
+        self.ops.get(idx + 1).is_some_and(|next| {
+            next.span == self.ops[idx].span && matches!(next.op, Op::MulAdd { .. } | Op::SetZero)
+        })
+    }
+
+    /// Replaces each innermost loop for which `rewrite` returns some [Op]s. `rewrite` is given the loop body and the
+    /// [Span] of the whole loop.
+    fn rewrite_loops<F>(self, mut rewrite: F) -> Self
+    where
+        F: FnMut(&[SpannedOp], Span) -> Option<Vec<SpannedOp>>,
+    {
+        let mut ops: Vec<SpannedOp> = Vec::with_capacity(self.ops.len());
+        let mut idx = 0;
+        while idx < self.ops.len() {
+            if self.ops[idx].op == Op::BeginLoop {
+                let close_idx = self.ops[idx + 1..]
+                    .iter()
+                    .position(|spanned_op| matches!(spanned_op.op, Op::BeginLoop | Op::EndLoop))
+                    .map(|len| idx + 1 + len)
+                    .filter(|&close_idx| self.ops[close_idx].op == Op::EndLoop);
+                if let Some(close_idx) = close_idx {
+                    let span = Span::new(self.ops[idx].span.start, self.ops[close_idx].span.end);
+                    if let Some(replacement) = rewrite(&self.ops[idx + 1..close_idx], span) {
+                        ops.extend(replacement);
+                        idx = close_idx + 1;
+                        continue;
+                    }
+                }
+            }
+            ops.push(self.ops[idx]);
+            idx += 1;
+        }
+        Self { ops }
+    }
+
+    /// Getter.
+    pub fn ops(&self) -> &[SpannedOp] {
+        self.ops.as_slice()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Should lower each instruction into one [Op].
+    #[test]
+    fn lower_instructions() {
+        let program = Program::new("", "<>+-,.[]");
+        let ir = Ir::new(&program);
+        let expected = vec![
+            Op::Move(-1),
+            Op::Move(1),
+            Op::Add {
+                offset: 0,
+                amount: 1,
+            },
+            Op::Add {
+                offset: 0,
+                amount: -1,
+            },
+            Op::Input,
+            Op::Output,
+            Op::BeginLoop,
+            Op::EndLoop,
+        ];
+        let actual: Vec<Op> = ir.ops().iter().map(|spanned_op| spanned_op.op()).collect();
+        assert_eq!(actual, expected);
+    }
+
+    /// Should fold runs of the same instruction and keep their spans.
+    #[test]
+    fn fold_runs() {
+        let program = Program::new("", "+++ comment --\n<<>[..]");
+        let ir = Ir::new(&program).fold();
+        let expected = vec![
+            SpannedOp::new(
+                Op::Add {
+                    offset: 0,
+                    amount: 3,
+                },
+                Span::new(0, 3),
+            ),
+            SpannedOp::new(
+                Op::Add {
+                    offset: 0,
+                    amount: -2,
+                },
+                Span::new(3, 5),
+            ),
+            SpannedOp::new(Op::Move(-2), Span::new(5, 7)),
+            SpannedOp::new(Op::Move(1), Span::new(7, 8)),
+            SpannedOp::new(Op::BeginLoop, Span::new(8, 9)),
+            SpannedOp::new(Op::Output, Span::new(9, 10)),
+            SpannedOp::new(Op::Output, Span::new(10, 11)),
+            SpannedOp::new(Op::EndLoop, Span::new(11, 12)),
+        ];
+        assert_eq!(ir.ops(), expected.as_slice());
+    }
+
+    /// Should replace clear loops only.
+    #[test]
+    fn replace_clear_loops() {
+        let program = Program::new("", "[-][+][--][-[-]]");
+        let ir = Ir::new(&program).clear_loops();
+        let expected = vec![
+            SpannedOp::new(Op::SetZero, Span::new(0, 3)),
+            SpannedOp::new(Op::SetZero, Span::new(3, 6)),
+            SpannedOp::new(Op::BeginLoop, Span::new(6, 7)),
+            SpannedOp::new(
+                Op::Add {
+                    offset: 0,
+                    amount: -1,
+                },
+                Span::new(7, 8),
+            ),
+            SpannedOp::new(
+                Op::Add {
+                    offset: 0,
+                    amount: -1,
+                },
+                Span::new(8, 9),
+            ),
+            SpannedOp::new(Op::EndLoop, Span::new(9, 10)),
+            SpannedOp::new(Op::BeginLoop, Span::new(10, 11)),
+            SpannedOp::new(
+                Op::Add {
+                    offset: 0,
+                    amount: -1,
+                },
+                Span::new(11, 12),
+            ),
+            SpannedOp::new(Op::SetZero, Span::new(12, 15)),
+            SpannedOp::new(Op::EndLoop, Span::new(15, 16)),
+        ];
+        assert_eq!(ir.ops(), expected.as_slice());
+    }
+
+    /// Should replace scan loops, whether they are folded or not.
+    #[test]
+    fn replace_scans() {
+        let program = Program::new("", "[>][<<][><]");
+        let ir = Ir::new(&program).scans();
+        let actual: Vec<Op> = ir.ops().iter().map(|spanned_op| spanned_op.op()).collect();
+        let expected = vec![
+            Op::Scan(1),
+            Op::Scan(-2),
+            Op::BeginLoop,
+            Op::Move(1),
+            Op::Move(-1),
+            Op::EndLoop,
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    /// Should replace multiply loops counting both down and up.
+    #[test]
+    fn replace_multiply_loops() {
+        let program = Program::new("", "[<+++>>>-<<+]");
+        let ir = Ir::new(&program).fold().multiply_loops();
+        let expected = vec![
+            SpannedOp::new(Op::Guard { min: -1, max: 2 }, Span::new(0, 13)),
+            SpannedOp::new(
+                Op::MulAdd {
+                    offset: -1,
+                    factor: -3,
+                },
+                Span::new(0, 13),
+            ),
+            SpannedOp::new(
+                Op::MulAdd {
+                    offset: 2,
+                    factor: 1,
+                },
+                Span::new(0, 13),
+            ),
+            SpannedOp::new(Op::SetZero, Span::new(0, 13)),
+        ];
+        assert_eq!(ir.ops(), expected.as_slice());
+    }
+
+    /// Should not replace loops which are not balanced or do not count the pointed cell down by one.
+    #[test]
+    fn not_replace_other_loops() {
+        let program = Program::new("", "[->+][-->+<][->.<]");
+        let ir = Ir::new(&program).fold().multiply_loops();
+        assert!(ir
+            .ops()
+            .iter()
+            .all(|spanned_op| !matches!(spanned_op.op(), Op::MulAdd { .. })));
+    }
+
+    /// Should replace moves in a basic block with offsets and a single move at its end.
+    #[test]
+    fn replace_moves_with_offsets() {
+        let program = Program::new("", "+>>-<<<+>.>+<<");
+        let ir = Ir::new(&program).fold().offsets();
+        let expected = vec![
+            SpannedOp::new(Op::Guard { min: -1, max: 2 }, Span::new(0, 9)),
+            SpannedOp::new(
+                Op::Add {
+                    offset: 0,
+                    amount: 1,
+                },
+                Span::new(0, 1),
+            ),
+            SpannedOp::new(
+                Op::Add {
+                    offset: 2,
+                    amount: -1,
+                },
+                Span::new(3, 4),
+            ),
+            SpannedOp::new(
+                Op::Add {
+                    offset: -1,
+                    amount: 1,
+                },
+                Span::new(7, 8),
+            ),
+            SpannedOp::new(Op::Output, Span::new(9, 10)),
+            SpannedOp::new(Op::Guard { min: -1, max: 1 }, Span::new(10, 14)),
+            SpannedOp::new(
+                Op::Add {
+                    offset: 1,
+                    amount: 1,
+                },
+                Span::new(11, 12),
+            ),
+            SpannedOp::new(Op::Move(-1), Span::new(10, 14)),
+        ];
+        assert_eq!(ir.ops(), expected.as_slice());
+    }
+
+    /// Should keep basic blocks with less than two moves as they are.
+    #[test]
+    fn keep_blocks_with_single_move() {
+        let program = Program::new("", "+>+");
+        let ir = Ir::new(&program).fold().offsets();
+        assert_eq!(ir, Ir::new(&program));
+    }
+
+    /// Should remove loops at the beginning and right after another loop, including replaced ones.
+    #[test]
+    fn remove_dead_code() {
+        let program = Program::new("", "[.][-]+[->+<][->+<]>[<]>>+<<[>]");
+        let ir = Ir::new(&program)
+            .fold()
+            .clear_loops()
+            .scans()
+            .multiply_loops()
+            .dead_code();
+        let actual: Vec<Op> = ir.ops().iter().map(|spanned_op| spanned_op.op()).collect();
+        let expected = vec![
+            Op::Add {
+                offset: 0,
+                amount: 1,
+            },
+            Op::Guard { min: 0, max: 1 },
+            Op::MulAdd {
+                offset: 1,
+                factor: 1,
+            },
+            Op::SetZero,
+            Op::Move(1),
+            Op::Scan(-1),
+            Op::Move(2),
+            Op::Add {
+                offset: 0,
+                amount: 1,
+            },
+            Op::Move(-2),
+            Op::Scan(1),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    /// Should not fold instructions cancelling each other.
+    #[test]
+    fn not_fold_opposite_instructions() {
+        let program = Program::new("", "+-<>");
+        let ir = Ir::new(&program).fold();
+        assert_eq!(ir.ops().len(), 4);
+    }
+}
@@ -1,8 +1,17 @@
 //! Converts text brainfuck code into Rust-understandable format.
 
+pub mod diagnostic;
+pub mod ir;
+mod named;
+pub mod pass_manager;
+pub mod snippet;
+
+use diagnostic::Diagnostic;
+use snippet::Snippet;
 use std::error::Error;
 use std::fmt;
 use std::fs::read_to_string;
+use std::ops::Deref;
 use std::path::{Path, PathBuf};
 
 /// A representation of the 8 Brainfuck instructions.
@@ -46,11 +55,17 @@ pub struct Instruction {
 #[derive(Debug)]
 pub struct Program {
     file_path: PathBuf,
+    source: String,
     instructions: Vec<Instruction>,
 }
 
-/// A representation for errors caused by incompatible brackets in Brainfuck source code.
+/// A brainfuck [Program] whose brackets all match. It can only be made by [Program::validated], so anything taking one
+/// never has to deal with unmatched brackets.
 #[derive(Debug)]
+pub struct ValidatedProgram(Program);
+
+/// A representation for errors caused by incompatible brackets in Brainfuck source code.
+#[derive(Debug, PartialEq)]
 pub enum IncompatibleBracket {
     /// A close bracket has no corresponding open bracket.
     MissingOpenBracket {
@@ -65,6 +80,18 @@ pub enum IncompatibleBracket {
     },
 }
 
+/// Every [IncompatibleBracket] of a [Program], sorted by where they are in the source code.
+#[derive(Debug, PartialEq)]
+pub struct IncompatibleBrackets(Vec<IncompatibleBracket>);
+
+/// A [Program] which [Program::validated] refused, along with its [IncompatibleBrackets], so that they can still be
+/// rendered against it without validating it again.
+#[derive(Debug)]
+pub struct InvalidProgram {
+    program: Program,
+    errors: IncompatibleBrackets,
+}
+
 impl RawInstruction {
     /// Convert a char value to BF [RawInstruction]. All brainfuck comment will be converted into None.
     /// # Example
@@ -148,8 +175,7 @@ impl Program {
     /// Creates a Brainfuck [Program] with a file name in a path-like format and its content in a string-like format.
     pub fn new<P: AsRef<Path>>(file_path: P, lines: &str) -> Self {
         let mut instructions: Vec<Instruction> = Vec::new();
-        let lines = lines.split('\n');
-        for (row, line) in lines.enumerate() {
+        for (row, line) in lines.split('\n').enumerate() {
             for (col, char) in line.chars().enumerate() {
                 if let Some(raw_instruction) = RawInstruction::from_char(char) {
                     instructions.push(Instruction::new(row + 1, col + 1, raw_instruction));
@@ -158,6 +184,7 @@ impl Program {
         }
         Self {
             file_path: file_path.as_ref().to_owned(),
+            source: lines.to_owned(),
             instructions,
         }
     }
@@ -175,7 +202,8 @@ impl Program {
         Ok(Program::new(file_path, lines))
     }
 
-    /// Check if a piece of Brainfuck [Program] is valid. I.e., if it has a matching brackets.
+    /// Check if a piece of Brainfuck [Program] is valid. I.e., if it has a matching brackets. Every bracket without a
+    /// matching one is reported, not only the first one.
     /// # Example
     /// ```no_run
     /// use bf_types::*;
@@ -186,28 +214,54 @@ impl Program {
     /// # Ok(())
     /// # }
     /// ```
-    pub fn validate(&self) -> Result<(), IncompatibleBracket> {
+    pub fn validate(&self) -> Result<(), IncompatibleBrackets> {
+        let mut errors: Vec<IncompatibleBracket> = Vec::new();
         let mut stack: Vec<&Instruction> = Vec::with_capacity(self.instructions().len());
-        for (_idx, ins) in self.instructions().iter().enumerate() {
+        for ins in self.instructions() {
             if ins.raw_instruction() == RawInstruction::BeginLoop {
                 stack.push(ins);
-            } else if ins.raw_instruction() == RawInstruction::EndLoop {
-                if stack.is_empty() {
-                    return Err(IncompatibleBracket::MissingOpenBracket {
-                        file_path: self.file_path().to_owned(),
-                        close_bracket: *ins,
-                    });
-                }
-                stack.pop();
+            } else if ins.raw_instruction() == RawInstruction::EndLoop && stack.pop().is_none() {
+                errors.push(IncompatibleBracket::MissingOpenBracket {
+                    file_path: self.file_path().to_owned(),
+                    close_bracket: *ins,
+                });
             }
         }
-        if let Some(ins) = stack.first() {
-            return Err(IncompatibleBracket::MissingCloseBracket {
-                file_path: self.file_path().to_owned(),
-                open_bracket: **ins,
-            });
+        // A close bracket only misses its open bracket while no open bracket is left unmatched, so every unmatched open
+        // bracket comes after them and the errors are already sorted.
+        errors.extend(
+            stack
+                .into_iter()
+                .map(|ins| IncompatibleBracket::MissingCloseBracket {
+                    file_path: self.file_path().to_owned(),
+                    open_bracket: *ins,
+                }),
+        );
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(IncompatibleBrackets(errors))
+        }
+    }
+
+    /// Turns this [Program] into a [ValidatedProgram] if it is valid, see [Program::validate].
+    /// # Example
+    /// ```
+    /// # use bf_types::*;
+    /// let program = Program::new("", "[-]>+").validated();
+    /// assert!(program.is_ok());
+    ///
+    /// let program = Program::new("", "[-").validated();
+    /// assert_eq!(program.unwrap_err().errors().len(), 1);
+    /// ```
+    pub fn validated(self) -> Result<ValidatedProgram, InvalidProgram> {
+        match self.validate() {
+            Ok(()) => Ok(ValidatedProgram(self)),
+            Err(errors) => Err(InvalidProgram {
+                program: self,
+                errors,
+            }),
         }
-        Ok(())
     }
 
     /// Getter.
@@ -219,6 +273,44 @@ impl Program {
     pub fn instructions(&self) -> &[Instruction] {
         self.instructions.as_slice()
     }
+
+    /// The source code this [Program] was parsed from, comments included.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The line of source code at `row`, counting from 1 like [Instruction::row] does.
+    /// # Example
+    /// ```
+    /// # use bf_types::*;
+    /// let program = Program::new("", "+\n[-] clear");
+    /// assert_eq!(program.line(2), Some("[-] clear"));
+    /// assert_eq!(program.line(3), None);
+    /// ```
+    pub fn line(&self, row: usize) -> Option<&str> {
+        self.source.split('\n').nth(row.checked_sub(1)?)
+    }
+
+    /// Offset of `instruction` from the start of the source code, in bytes, if it is in this [Program].
+    /// # Example
+    /// ```
+    /// # use bf_types::*;
+    /// let program = Program::new("", "é+\n-");
+    /// assert_eq!(program.byte_offset(program.instructions()[0]), Some(2));
+    /// assert_eq!(program.byte_offset(program.instructions()[1]), Some(4));
+    /// ```
+    pub fn byte_offset(&self, instruction: Instruction) -> Option<usize> {
+        let rows_before = instruction.row().checked_sub(1)?;
+        let line_offset: usize = self
+            .source
+            .split('\n')
+            .take(rows_before)
+            .map(|line| line.len() + 1)
+            .sum();
+        let line = self.line(instruction.row())?;
+        let (col_offset, _) = line.char_indices().nth(instruction.col().checked_sub(1)?)?;
+        Some(line_offset + col_offset)
+    }
 }
 
 impl fmt::Display for Program {
@@ -237,6 +329,125 @@ impl fmt::Display for Program {
     }
 }
 
+impl ValidatedProgram {
+    /// Gives back the [Program] this was validated from.
+    pub fn into_inner(self) -> Program {
+        self.0
+    }
+}
+
+impl Deref for ValidatedProgram {
+    type Target = Program;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl IncompatibleBracket {
+    /// The bracket which has no matching one.
+    pub fn bracket(&self) -> Instruction {
+        match self {
+            IncompatibleBracket::MissingOpenBracket { close_bracket, .. } => *close_bracket,
+            IncompatibleBracket::MissingCloseBracket { open_bracket, .. } => *open_bracket,
+        }
+    }
+
+    /// The bracket which was most likely meant to match [IncompatibleBracket::bracket], i.e., the closest open bracket
+    /// before a close bracket or the closest close bracket after an open bracket. `program` has to be the [Program]
+    /// this error was found in.
+    pub fn likely_partner(&self, program: &Program) -> Option<Instruction> {
+        let bracket = self.bracket();
+        let instructions = program.instructions().iter();
+        match self {
+            IncompatibleBracket::MissingOpenBracket { .. } => instructions
+                .rev()
+                .skip_while(|ins| **ins != bracket)
+                .find(|ins| ins.raw_instruction() == RawInstruction::BeginLoop),
+            IncompatibleBracket::MissingCloseBracket { .. } => instructions
+                .skip_while(|ins| **ins != bracket)
+                .find(|ins| ins.raw_instruction() == RawInstruction::EndLoop),
+        }
+        .copied()
+    }
+
+    /// Stable code of this error, see [diagnostic].
+    pub fn code(&self) -> &'static str {
+        match self {
+            IncompatibleBracket::MissingOpenBracket { .. } => "E0001",
+            IncompatibleBracket::MissingCloseBracket { .. } => "E0002",
+        }
+    }
+
+    /// What is wrong, without where it is unlike its [fmt::Display], for when the location is shown on its own.
+    pub fn message(&self) -> &'static str {
+        match self {
+            IncompatibleBracket::MissingOpenBracket { .. } => "unmatched ']'",
+            IncompatibleBracket::MissingCloseBracket { .. } => "unmatched '['",
+        }
+    }
+
+    /// Describes this error as a [Diagnostic]. `program` has to be the [Program] this error was found in.
+    pub fn diagnostic(&self, program: &Program) -> Diagnostic {
+        Diagnostic::new(self.code(), self.message(), program.file_path())
+            .with_location(program, self.bracket())
+    }
+
+    /// Renders this error like rustc does, with the line of `program` it is on and its likely partner, see
+    /// [IncompatibleBracket::likely_partner]. `program` has to be the [Program] this error was found in.
+    /// # Example
+    /// ```
+    /// # use bf_types::*;
+    /// let program = Program::new("hello.bf", "+[[-]");
+    /// let error = program.validate().unwrap_err().into_iter().next().unwrap();
+    /// eprintln!("{}", error.snippet(&program).with_color(true));
+    /// ```
+    pub fn snippet<'a>(&self, program: &'a Program) -> Snippet<'a> {
+        let (label, partner_label) = match self {
+            IncompatibleBracket::MissingOpenBracket { .. } => ("no matching '['", "closest '['"),
+            IncompatibleBracket::MissingCloseBracket { .. } => ("no matching ']'", "closest ']'"),
+        };
+        let snippet = Snippet::new(program, self.message()).with_label(self.bracket(), label);
+        match self.likely_partner(program) {
+            Some(partner) => snippet.with_secondary_label(partner, partner_label),
+            None => snippet,
+        }
+    }
+}
+
+impl IncompatibleBrackets {
+    /// Getter.
+    pub fn errors(&self) -> &[IncompatibleBracket] {
+        &self.0
+    }
+}
+
+impl InvalidProgram {
+    /// Getter.
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    /// Every [IncompatibleBracket] of the [Program], sorted by where they are in the source code.
+    pub fn errors(&self) -> &[IncompatibleBracket] {
+        self.errors.errors()
+    }
+
+    /// Gives back the [Program] which failed validation.
+    pub fn into_program(self) -> Program {
+        self.program
+    }
+}
+
+impl IntoIterator for IncompatibleBrackets {
+    type Item = IncompatibleBracket;
+    type IntoIter = std::vec::IntoIter<IncompatibleBracket>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
 impl fmt::Display for IncompatibleBracket {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -270,6 +481,28 @@ impl fmt::Display for IncompatibleBracket {
 
 impl Error for IncompatibleBracket {}
 
+impl fmt::Display for IncompatibleBrackets {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, error) in self.errors().iter().enumerate() {
+            if idx > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for IncompatibleBrackets {}
+
+impl fmt::Display for InvalidProgram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.errors)
+    }
+}
+
+impl Error for InvalidProgram {}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -288,10 +521,8 @@ mod tests {
             RawInstruction::EndLoop,
         ];
         let parsed_program = Program::new("file_path", bf_code);
-        for (actual_instruction, expected_instruction) in parsed_program
-            .instructions()
-            .iter()
-            .zip(expected.into_iter())
+        for (actual_instruction, expected_instruction) in
+            parsed_program.instructions().iter().zip(expected)
         {
             if actual_instruction.raw_instruction() != expected_instruction {
                 return Err(format!(
@@ -338,10 +569,8 @@ mod tests {
                 raw_instruction: RawInstruction::Decrement,
             },
         ];
-        for (actual_instruction, expected_instruction) in parsed_program
-            .instructions()
-            .iter()
-            .zip(expected.into_iter())
+        for (actual_instruction, expected_instruction) in
+            parsed_program.instructions().iter().zip(expected)
         {
             if *actual_instruction != expected_instruction {
                 return Err(format!(
@@ -360,16 +589,23 @@ mod tests {
         let result = program.validate();
 
         assert!(result.is_err());
-        match result {
-            Err(IncompatibleBracket::MissingCloseBracket {
+        match result.unwrap_err().errors() {
+            [IncompatibleBracket::MissingCloseBracket {
                 file_path,
                 open_bracket,
-            }) => {
+            }, IncompatibleBracket::MissingCloseBracket {
+                open_bracket: second_open_bracket,
+                ..
+            }] => {
                 assert_eq!(file_path.to_str().unwrap(), "");
                 assert_eq!(
-                    open_bracket,
+                    *open_bracket,
                     Instruction::new(1, 1, RawInstruction::BeginLoop)
                 );
+                assert_eq!(
+                    *second_open_bracket,
+                    Instruction::new(1, 2, RawInstruction::BeginLoop)
+                );
             }
             _ => panic!("Unrecognized error"),
         }
@@ -382,14 +618,14 @@ mod tests {
         let result = program.validate();
 
         assert!(result.is_err());
-        match result {
-            Err(IncompatibleBracket::MissingOpenBracket {
+        match result.unwrap_err().errors() {
+            [IncompatibleBracket::MissingOpenBracket {
                 file_path,
                 close_bracket,
-            }) => {
+            }] => {
                 assert_eq!(file_path.to_str().unwrap(), "");
                 assert_eq!(
-                    close_bracket,
+                    *close_bracket,
                     Instruction::new(1, 1, RawInstruction::EndLoop)
                 );
             }
@@ -397,6 +633,37 @@ mod tests {
         }
     }
 
+    /// Should identify every incompatible bracket in Brainfuck source code, in the order they appear.
+    #[test]
+    fn should_identify_every_incompatible_bracket() {
+        let program = Program::new("", "]+]\n[[-]]]\n[>[");
+        let brackets: Vec<Instruction> = program
+            .validate()
+            .unwrap_err()
+            .into_iter()
+            .map(|error| error.bracket())
+            .collect();
+
+        assert_eq!(
+            brackets,
+            vec![
+                Instruction::new(1, 1, RawInstruction::EndLoop),
+                Instruction::new(1, 3, RawInstruction::EndLoop),
+                Instruction::new(2, 6, RawInstruction::EndLoop),
+                Instruction::new(3, 1, RawInstruction::BeginLoop),
+                Instruction::new(3, 3, RawInstruction::BeginLoop),
+            ]
+        );
+        assert_eq!(
+            program.validate().unwrap_err().to_string(),
+            "Found ']' at [:1:1] but no matching '[' found\n\
+             Found ']' at [:1:3] but no matching '[' found\n\
+             Found ']' at [:2:6] but no matching '[' found\n\
+             Found '[' at [:3:1] but no matching ']' found\n\
+             Found '[' at [:3:3] but no matching ']' found"
+        );
+    }
+
     /// Should identify matching brackets in Brainfuck source code.
     #[test]
     fn should_identify_matching_brackets() {
@@ -405,4 +672,45 @@ mod tests {
 
         assert!(result.is_ok());
     }
+
+    /// Should point at the closest bracket which could have matched an incompatible one.
+    #[test]
+    fn should_point_at_likely_partner() {
+        let program = Program::new("f.bf", "[[-]\n+]]");
+        let errors: Vec<IncompatibleBracket> =
+            program.validate().unwrap_err().into_iter().collect();
+
+        assert_eq!(
+            errors[0].likely_partner(&program),
+            Some(Instruction::new(1, 2, RawInstruction::BeginLoop))
+        );
+        assert_eq!(
+            errors[0].snippet(&program).to_string(),
+            indoc!(
+                "
+                error: unmatched ']'
+                 --> f.bf:2:3
+                  |
+                1 | [[-]
+                  |  - closest '['
+                2 | +]]
+                  |   ^ no matching '['"
+            )
+            .trim_start()
+        );
+    }
+
+    /// Should only validate a program with matching brackets, keeping all of its instructions.
+    #[test]
+    fn should_validate_matching_brackets_only() {
+        let program = Program::new("file_path", "+[-]>").validated().unwrap();
+        assert_eq!(program.file_path(), Path::new("file_path"));
+        assert_eq!(program.instructions().len(), 5);
+
+        let result = Program::new("file_path", "+[-]]").validated();
+        assert!(matches!(
+            result.unwrap_err().errors(),
+            [IncompatibleBracket::MissingOpenBracket { .. }]
+        ));
+    }
 }
@@ -0,0 +1,67 @@
+//! Gives fieldless enums the names they go by on the command line.
+
+/// Implements, for a fieldless enum, `ALL` listing every variant in the given order, `name` giving the name a variant
+/// goes by on the command line, and [Display](std::fmt::Display) and [FromStr](std::str::FromStr) going from one to
+/// the other. Parse errors refer to the enum as `$what`.
+/// # Example
+/// ```
+/// #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// enum Speed {
+///     Slow,
+///     Fast,
+/// }
+///
+/// bf_types::impl_named!(Speed, "speed", {
+///     Slow => "slow",
+///     Fast => "fast",
+/// });
+///
+/// assert_eq!(Speed::ALL, [Speed::Slow, Speed::Fast]);
+/// assert_eq!(Speed::Fast.to_string(), "fast");
+/// assert_eq!("slow".parse::<Speed>(), Ok(Speed::Slow));
+/// assert_eq!(
+///     "warp".parse::<Speed>(),
+///     Err("unknown speed 'warp', expected one of slow, fast".to_owned())
+/// );
+/// ```
+#[macro_export]
+macro_rules! impl_named {
+    ($name:ident, $what:literal, { $($variant:ident => $text:literal),+ $(,)? }) => {
+        impl $name {
+            #[doc = concat!("Every [", stringify!($name), "].")]
+            pub const ALL: [$name; [$(stringify!($variant)),+].len()] = [$($name::$variant),+];
+
+            /// The name used on the command line.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $($name::$variant => $text),+
+                }
+            }
+        }
+
+        impl ::std::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                write!(f, "{}", self.name())
+            }
+        }
+
+        impl ::std::str::FromStr for $name {
+            type Err = String;
+
+            #[doc = concat!("Parse one from its [", stringify!($name), "::name].")]
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $name::ALL
+                    .into_iter()
+                    .find(|variant| variant.name() == s)
+                    .ok_or_else(|| {
+                        let names: Vec<&str> = $name::ALL.iter().map(|v| v.name()).collect();
+                        format!(
+                            concat!("unknown ", $what, " '{}', expected one of {}"),
+                            s,
+                            names.join(", ")
+                        )
+                    })
+            }
+        }
+    };
+}
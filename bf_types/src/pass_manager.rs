@@ -0,0 +1,199 @@
+//! Picks which optimization passes are run when lowering a [Program] into its [Ir].
+
+use crate::ir::Ir;
+use crate::{impl_named, Program};
+use std::str::FromStr;
+
+/// An optimization pass on an [Ir]. [Pass::ALL] lists every pass in the order they run.
+/// # Example
+/// ```
+/// # use bf_types::pass_manager::Pass;
+/// assert_eq!("clear-loop".parse::<Pass>(), Ok(Pass::ClearLoop));
+/// assert!("unroll".parse::<Pass>().is_err());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Fold runs of the same instruction, see [Ir::fold].
+    Fold,
+
+    /// Replace clear loops, see [Ir::clear_loops].
+    ClearLoop,
+
+    /// Replace scan loops, see [Ir::scans].
+    Scan,
+
+    /// Replace multiply loops, see [Ir::multiply_loops].
+    Multiply,
+
+    /// Replace pointer moves with offsets, see [Ir::offsets].
+    Offset,
+
+    /// Remove loops which can never run, see [Ir::dead_code].
+    DeadCode,
+}
+
+/// How hard the [PassManager] tries to optimize.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptLevel {
+    /// No optimization, one operation per source instruction.
+    O0,
+
+    /// Only fold runs of the same instruction.
+    O1,
+
+    /// Every pass except [Pass::DeadCode].
+    #[default]
+    O2,
+
+    /// Every pass, including [Pass::DeadCode] which assumes the VM memory starts with zero cells.
+    O3,
+}
+
+/// A set of [Pass]es to run on an [Ir]. Passes always run in the order of [Pass::ALL], no matter the order they are
+/// enabled in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PassManager {
+    passes: Vec<Pass>,
+}
+
+impl_named!(Pass, "pass", {
+    Fold => "fold",
+    ClearLoop => "clear-loop",
+    Scan => "scan",
+    Multiply => "multiply",
+    Offset => "offset",
+    DeadCode => "dead-code",
+});
+
+impl Pass {
+    /// Run this pass on an [Ir].
+    pub fn run(&self, ir: Ir) -> Ir {
+        match self {
+            Pass::Fold => ir.fold(),
+            Pass::ClearLoop => ir.clear_loops(),
+            Pass::Scan => ir.scans(),
+            Pass::Multiply => ir.multiply_loops(),
+            Pass::Offset => ir.offsets(),
+            Pass::DeadCode => ir.dead_code(),
+        }
+    }
+}
+
+impl OptLevel {
+    /// The [Pass]es enabled at this level.
+    pub fn passes(&self) -> &'static [Pass] {
+        match self {
+            OptLevel::O0 => &[],
+            OptLevel::O1 => &[Pass::Fold],
+            OptLevel::O2 => &Pass::ALL[..5],
+            OptLevel::O3 => &Pass::ALL,
+        }
+    }
+}
+
+impl FromStr for OptLevel {
+    type Err = String;
+
+    /// Parse an optimization level from `0` to `3`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "0" => Ok(OptLevel::O0),
+            "1" => Ok(OptLevel::O1),
+            "2" => Ok(OptLevel::O2),
+            "3" => Ok(OptLevel::O3),
+            _ => Err(format!(
+                "unknown optimization level '{}', expected 0 to 3",
+                s
+            )),
+        }
+    }
+}
+
+impl PassManager {
+    /// Creates a [PassManager] running the passes of an [OptLevel].
+    /// # Example
+    /// ```
+    /// # use bf_types::Program;
+    /// # use bf_types::pass_manager::*;
+    /// let program = Program::new("", "[-]>>>");
+    /// let passes = PassManager::new(OptLevel::O1).with(Pass::ClearLoop);
+    /// let ir = passes.run(&program);
+    /// assert_eq!(ir.ops().len(), 2);
+    /// ```
+    pub fn new(level: OptLevel) -> Self {
+        Self {
+            passes: level.passes().to_vec(),
+        }
+    }
+
+    /// Enables a [Pass].
+    pub fn with(mut self, pass: Pass) -> Self {
+        if !self.is_enabled(pass) {
+            self.passes.push(pass);
+            self.passes
+                .sort_by_key(|enabled| Pass::ALL.iter().position(|p| p == enabled));
+        }
+        self
+    }
+
+    /// Disables a [Pass].
+    pub fn without(mut self, pass: Pass) -> Self {
+        self.passes.retain(|enabled| *enabled != pass);
+        self
+    }
+
+    /// If a [Pass] is enabled.
+    pub fn is_enabled(&self, pass: Pass) -> bool {
+        self.passes.contains(&pass)
+    }
+
+    /// Lowers a [Program] into its [Ir] and runs every enabled [Pass] on it.
+    pub fn run(&self, program: &Program) -> Ir {
+        Pass::ALL
+            .into_iter()
+            .filter(|pass| self.is_enabled(*pass))
+            .fold(Ir::new(program), |ir, pass| pass.run(ir))
+    }
+}
+
+impl Default for PassManager {
+    fn default() -> Self {
+        Self::new(OptLevel::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Should run nothing at level 0.
+    #[test]
+    fn run_nothing_at_level_0() {
+        let program = Program::new("", "+++[-]>>[>]");
+        let ir = PassManager::new(OptLevel::O0).run(&program);
+        assert_eq!(ir, Ir::new(&program));
+    }
+
+    /// Should run passes in a fixed order no matter the order they are enabled in.
+    #[test]
+    fn run_passes_in_fixed_order() {
+        let program = Program::new("", "[->>+<<]");
+        let enabled_later = PassManager::new(OptLevel::O0)
+            .with(Pass::Multiply)
+            .with(Pass::Fold)
+            .run(&program);
+        let enabled_first = PassManager::new(OptLevel::O0)
+            .with(Pass::Fold)
+            .with(Pass::Multiply)
+            .run(&program);
+        assert_eq!(enabled_later, enabled_first);
+        assert_eq!(enabled_later, Ir::new(&program).fold().multiply_loops());
+    }
+
+    /// Should not run disabled passes.
+    #[test]
+    fn not_run_disabled_passes() {
+        let passes = PassManager::new(OptLevel::O3).without(Pass::DeadCode);
+        assert_eq!(passes, PassManager::new(OptLevel::O2));
+    }
+}
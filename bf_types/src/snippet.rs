@@ -0,0 +1,302 @@
+//! Renders an error about some instructions of a [Program] the way rustc renders its errors, i.e., with the lines of
+//! source code they are on and a caret under each of them.
+
+use crate::{Instruction, Program};
+use std::fmt;
+
+/// ANSI escape code for an error and its primary label, in bold red.
+const ERROR_STYLE: &str = "\x1b[1;31m";
+
+/// ANSI escape code for the message, in bold.
+const MESSAGE_STYLE: &str = "\x1b[1m";
+
+/// ANSI escape code for the gutter and secondary labels, in bold blue.
+const GUTTER_STYLE: &str = "\x1b[1;34m";
+
+/// ANSI escape code going back to the default style.
+const RESET: &str = "\x1b[0m";
+
+/// How many columns a tab takes up once rendered.
+const TAB_WIDTH: usize = 4;
+
+/// An error message about a [Program] along with the [Instruction]s it is about, rendered by its [fmt::Display].
+/// # Example
+/// ```
+/// # use bf_types::*;
+/// use bf_types::snippet::Snippet;
+/// let program = Program::new("hello.bf", "+[\n-]]");
+/// let snippet = Snippet::new(&program, "unmatched bracket")
+///     .with_label(program.instructions()[4], "this one")
+///     .with_secondary_label(program.instructions()[1], "closest '['");
+/// assert_eq!(
+///     snippet.to_string(),
+///     "error: unmatched bracket
+///  --> hello.bf:2:3
+///   |
+/// 1 | +[
+///   |  - closest '['
+/// 2 | -]]
+///   |   ^ this one"
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Snippet<'a> {
+    program: &'a Program,
+    message: String,
+    labels: Vec<Label>,
+
+    /// Shown beneath the source code, in the order they were added.
+    notes: Vec<String>,
+    color: bool,
+}
+
+/// A marker under an [Instruction] of a [Snippet].
+#[derive(Debug, Clone)]
+struct Label {
+    instruction: Instruction,
+    text: String,
+
+    /// If the marker is a caret, pointing at what the error is about, rather than something related to it.
+    primary: bool,
+}
+
+impl<'a> Snippet<'a> {
+    /// Creates a [Snippet] about `program` with no labels and no color.
+    pub fn new(program: &'a Program, message: impl fmt::Display) -> Self {
+        Self {
+            program,
+            message: message.to_string(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            color: false,
+        }
+    }
+
+    /// Points a caret at `instruction`, telling what is wrong with it. The first one is where the error is reported at.
+    pub fn with_label(mut self, instruction: Instruction, text: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            instruction,
+            text: text.into(),
+            primary: true,
+        });
+        self
+    }
+
+    /// Underlines `instruction`, telling how it is related to the error.
+    pub fn with_secondary_label(
+        mut self,
+        instruction: Instruction,
+        text: impl Into<String>,
+    ) -> Self {
+        self.labels.push(Label {
+            instruction,
+            text: text.into(),
+            primary: false,
+        });
+        self
+    }
+
+    /// Adds a note beneath the source code, about anything which does not point at an instruction.
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Sets if the [Snippet] is rendered with ANSI colors, it is not by default.
+    pub fn with_color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Getter.
+    pub fn color(&self) -> bool {
+        self.color
+    }
+
+    /// `text` wrapped in the ANSI escape codes of `style`, if the [Snippet] has colors.
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_owned()
+        }
+    }
+
+    /// Writes every note beneath the gutter, which is `gutter` wide.
+    fn fmt_notes(&self, f: &mut fmt::Formatter<'_>, gutter: &str) -> fmt::Result {
+        for note in &self.notes {
+            let note = format!("{} {}", self.paint(MESSAGE_STYLE, "note:"), note);
+            write!(f, "\n{} {} {}", gutter, self.paint(GUTTER_STYLE, "="), note)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Snippet<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}{}",
+            self.paint(ERROR_STYLE, "error"),
+            self.paint(MESSAGE_STYLE, &format!(": {}", self.message))
+        )?;
+        let Some(reported) = self
+            .labels
+            .iter()
+            .find(|label| label.primary)
+            .or(self.labels.first())
+        else {
+            return self.fmt_notes(f, "");
+        };
+
+        let mut labels: Vec<&Label> = self.labels.iter().collect();
+        labels.sort_by_key(|label| (label.instruction.row(), label.instruction.col()));
+        let width = labels
+            .iter()
+            .map(|label| label.instruction.row().to_string().len())
+            .max()
+            .unwrap_or_default();
+        let gutter = " ".repeat(width);
+        let bar = self.paint(GUTTER_STYLE, "|");
+        write!(
+            f,
+            "\n{}{} {}:{}:{}\n{} {}",
+            gutter,
+            self.paint(GUTTER_STYLE, "-->"),
+            self.program.file_path().display(),
+            reported.instruction.row(),
+            reported.instruction.col(),
+            gutter,
+            bar
+        )?;
+
+        let mut previous_row: Option<usize> = None;
+        for label in labels {
+            let row = label.instruction.row();
+            let line = self.program.line(row).unwrap_or_default();
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            if previous_row != Some(row) {
+                if previous_row.is_some_and(|previous_row| row > previous_row + 1) {
+                    write!(f, "\n{}", self.paint(GUTTER_STYLE, "..."))?;
+                }
+                let number = self.paint(GUTTER_STYLE, &format!("{row:>width$}"));
+                let line: String = line
+                    .chars()
+                    .map(|c| {
+                        if c == '\t' {
+                            " ".repeat(TAB_WIDTH)
+                        } else {
+                            c.to_string()
+                        }
+                    })
+                    .collect();
+                write!(f, "\n{} {} {}", number, bar, line)?;
+                previous_row = Some(row);
+            }
+
+            let indent: usize = line
+                .chars()
+                .take(label.instruction.col() - 1)
+                .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+                .sum();
+            let (style, marker) = if label.primary {
+                (ERROR_STYLE, '^')
+            } else {
+                (GUTTER_STYLE, '-')
+            };
+            let marker = self.paint(style, &format!("{} {}", marker, label.text));
+            write!(f, "\n{} {} {}{}", gutter, bar, " ".repeat(indent), marker)?;
+        }
+        if !self.notes.is_empty() {
+            write!(f, "\n{} {}", gutter, bar)?;
+        }
+        self.fmt_notes(f, &gutter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RawInstruction;
+    use indoc::indoc;
+
+    /// Should render only the message if there is nothing to point at.
+    #[test]
+    fn render_message_only() {
+        let program = Program::new("", "+");
+        let snippet = Snippet::new(&program, "something went wrong");
+
+        assert_eq!(snippet.to_string(), "error: something went wrong");
+    }
+
+    /// Should keep a gutter as wide as the largest row, skip rows between labels and expand tabs.
+    #[test]
+    fn render_far_apart_labels() {
+        let source = "[\n\n\n\n\n\n\n\n\n\t+ ]";
+        let program = Program::new("file.bf", source);
+        let snippet = Snippet::new(&program, "message")
+            .with_secondary_label(Instruction::new(1, 1, RawInstruction::BeginLoop), "open")
+            .with_label(Instruction::new(10, 4, RawInstruction::EndLoop), "close");
+
+        assert_eq!(
+            snippet.to_string(),
+            indoc!(
+                "
+                error: message
+                  --> file.bf:10:4
+                   |
+                 1 | [
+                   | - open
+                ...
+                10 |     + ]
+                   |       ^ close"
+            )
+            .trim_start()
+        );
+    }
+
+    /// Should render notes beneath the source code, or right beneath the message if there is nothing to point at.
+    #[test]
+    fn render_notes() {
+        let program = Program::new("f", "+>");
+        let snippet = Snippet::new(&program, "message")
+            .with_label(program.instructions()[1], "here")
+            .with_note("first")
+            .with_note("second");
+
+        assert_eq!(
+            snippet.to_string(),
+            indoc!(
+                "
+                error: message
+                 --> f:1:2
+                  |
+                1 | +>
+                  |  ^ here
+                  |
+                  = note: first
+                  = note: second"
+            )
+            .trim_start()
+        );
+
+        let snippet = Snippet::new(&program, "message").with_note("first");
+        assert_eq!(snippet.to_string(), "error: message\n = note: first");
+    }
+
+    /// Should wrap each part in ANSI escape codes when rendering with colors.
+    #[test]
+    fn render_with_color() {
+        let program = Program::new("f", "<");
+        let snippet = Snippet::new(&program, "message")
+            .with_label(program.instructions()[0], "here")
+            .with_color(true);
+
+        assert_eq!(
+            snippet.to_string(),
+            "\x1b[1;31merror\x1b[0m\x1b[1m: message\x1b[0m\n \
+             \x1b[1;34m-->\x1b[0m f:1:1\n  \x1b[1;34m|\x1b[0m\n\
+             \x1b[1;34m1\x1b[0m \x1b[1;34m|\x1b[0m <\n  \x1b[1;34m|\x1b[0m \x1b[1;31m^ here\x1b[0m"
+        );
+    }
+}
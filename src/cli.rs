@@ -1,5 +1,11 @@
-use clap::Parser;
-use std::{num::NonZeroUsize, path::PathBuf};
+use bf_interp::eof_policy::EofPolicy;
+use bf_interp::flush_policy::FlushPolicy;
+use bf_interp::overflow_policy::OverflowPolicy;
+use bf_interp::tape::{Backend, GrowthPolicy, Topology};
+use bf_types::pass_manager::{OptLevel, Pass};
+use clap::{Parser, ValueEnum};
+use std::io::{stderr, IsTerminal};
+use std::{num::NonZeroUsize, path::PathBuf, time::Duration};
 
 /// A Brainfuck interpreter written in Rust.
 #[derive(Parser, Debug)]
@@ -12,7 +18,136 @@ pub(crate) struct Args {
     #[arg(short, long, default_value = "30000")]
     pub(crate) cells: NonZeroUsize,
 
+    /// The type of each cell. `,` stores the byte read as a value from 0 to 255 (i8 cells reinterpret it), and `.`
+    /// writes the cell value modulo 256.
+    #[arg(long = "cell-size", value_enum, default_value = "u8")]
+    pub(crate) cell_size: CellSize,
+
+    /// What to do when a cell goes beyond its max value or below its min value, one of wrap, saturate and error.
+    #[arg(long = "overflow", default_value = "wrap")]
+    pub(crate) overflow_policy: OverflowPolicy,
+
     /// Allow virtual machine memory to auto extend or not.
     #[arg(short, long)]
     pub(crate) extensible: bool,
+
+    /// What happens when the pointer moves beyond an edge of the memory, one of bounded, extensible, bidirectional and
+    /// circular. A bidirectional memory also extends to the left, giving cells with negative indices, while a circular
+    /// one wraps the pointer around to the other edge. Defaults to extensible if `--extensible` is set, otherwise
+    /// bounded.
+    #[arg(long = "tape", conflicts_with = "extensible")]
+    pub(crate) topology: Option<Topology>,
+
+    /// How the memory stores its cells, one of dense and sparse. A sparse memory only allocates pages of cells which
+    /// are written to, so a program may walk far away without allocating every cell on its way.
+    #[arg(long = "tape-backend", default_value = "dense")]
+    pub(crate) backend: Backend,
+
+    /// The most bytes memory cells may take up, the program fails instead of allocating more. Unlimited by default.
+    #[arg(long = "max-memory")]
+    pub(crate) max_memory: Option<usize>,
+
+    /// The most cells memory may have, the program fails instead of growing it further. Unlimited by default.
+    #[arg(long = "max-cells")]
+    pub(crate) max_cells: Option<usize>,
+
+    /// How much memory grows once the pointer moves beyond its edge, one of doubling, exact, and `increment:<cells>`.
+    #[arg(long = "growth", default_value = "doubling")]
+    pub(crate) growth_policy: GrowthPolicy,
+
+    /// The most steps the program may run, it fails instead of running any further. A step is one optimized
+    /// instruction, or one move of the pointer while scanning for a zero cell, so higher optimization levels need fewer
+    /// steps. Unlimited by default.
+    #[arg(long = "max-steps")]
+    pub(crate) max_steps: Option<usize>,
+
+    /// The most seconds the program may run, e.g., 2.5, it fails instead of running any further. A program waiting for
+    /// input only stops once it gets some. Unlimited by default.
+    #[arg(long = "timeout", value_parser = parse_timeout)]
+    pub(crate) timeout: Option<Duration>,
+
+    /// Optimization level from 0 to 3. Level 0 interprets one instruction at a time, level 1 folds runs of the same
+    /// instruction, level 2 also replaces loop idioms and pointer moves, and level 3 also removes dead code.
+    #[arg(short = 'O', default_value = "2")]
+    pub(crate) opt_level: OptLevel,
+
+    /// Run an optimization pass on top of the optimization level, one of fold, clear-loop, scan, multiply, offset and
+    /// dead-code. Can be used multiple times.
+    #[arg(long = "pass")]
+    pub(crate) passes: Vec<Pass>,
+
+    /// Do not run an optimization pass even if the optimization level includes it. Can be used multiple times.
+    #[arg(long = "no-pass")]
+    pub(crate) no_passes: Vec<Pass>,
+
+    /// When to flush the output, one of always, newline, before-input and at-exit. Output is always flushed before the
+    /// program exits.
+    #[arg(long = "flush", default_value = "newline")]
+    pub(crate) flush_policy: FlushPolicy,
+
+    /// What to do when the program reads input after it has run out, one of error, unchanged, zero and minus-one.
+    #[arg(long = "eof", default_value = "error")]
+    pub(crate) eof_policy: EofPolicy,
+
+    /// How many cells on each side of the pointer are shown beneath an error at run time, along with the pointer, the
+    /// program counter and the number of steps run.
+    #[arg(long = "context-cells", default_value = "4")]
+    pub(crate) context_cells: usize,
+
+    /// When to color errors, one of auto, always and never. Auto colors them only if stderr is a terminal.
+    #[arg(long = "color", value_enum, default_value = "auto")]
+    pub(crate) color: ColorChoice,
+
+    /// How errors are printed, one of human and json. Json prints one object per error and line, each with a stable
+    /// error code, its severity, file, row, col, byte offset and message, and for errors at run time the pointer and
+    /// the value of its cell.
+    #[arg(long = "error-format", value_enum, default_value = "human")]
+    pub(crate) error_format: ErrorFormat,
+}
+
+/// Every type a virtual machine cell can be.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum CellSize {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+}
+
+/// When errors are colored.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// If errors printed to stderr should be colored.
+    pub(crate) fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Auto => stderr().is_terminal(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Every format errors can be printed in.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub(crate) enum ErrorFormat {
+    Human,
+    Json,
+}
+
+/// Parse a `--timeout` given in seconds.
+fn parse_timeout(s: &str) -> Result<Duration, String> {
+    let seconds: f64 = s
+        .parse()
+        .map_err(|_| format!("invalid timeout '{}', expected seconds", s))?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("invalid timeout '{}': {}", s, e))
 }
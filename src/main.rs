@@ -1,18 +1,121 @@
+use bf_interp::cancellation::CancellationToken;
+use bf_interp::cell_kind::CellKind;
+use bf_interp::tape::Topology;
 use bf_interp::VM;
+use bf_types::diagnostic::Diagnostic;
+use bf_types::pass_manager::PassManager;
+use bf_types::snippet::Snippet;
+use bf_types::{Program, ValidatedProgram};
 use clap::Parser;
+use cli::{CellSize, ErrorFormat};
 use std::io::{stdin, stdout};
 use std::process::ExitCode;
+use std::thread;
 
 mod cli;
 
 fn run_bf(args: cli::Args) -> Result<(), Box<dyn std::error::Error>> {
-    let program = bf_types::Program::from_file(args.program)?;
-    program.validate()?;
-    let mut virtual_machine: VM<u8> = VM::new(args.cells, args.extensible, &program);
-    virtual_machine.interpret(&mut stdin(), &mut stdout())?;
+    let program = Program::from_file(&args.program).map_err(|e| match args.error_format {
+        ErrorFormat::Human => e.to_string(),
+        ErrorFormat::Json => to_json(&Diagnostic::new("E0003", e, &args.program)),
+    })?;
+    let program = match program.validated() {
+        Ok(program) => program,
+        Err(invalid) => {
+            let program = invalid.program();
+            let errors = invalid
+                .errors()
+                .iter()
+                .map(|error| (error.snippet(program), error.diagnostic(program)));
+            return Err(render_errors(&args, errors).into());
+        }
+    };
+    let passes = args
+        .passes
+        .iter()
+        .fold(PassManager::new(args.opt_level), |passes, pass| {
+            passes.with(*pass)
+        });
+    let passes = args
+        .no_passes
+        .iter()
+        .fold(passes, |passes, pass| passes.without(*pass));
+    match args.cell_size {
+        CellSize::U8 => run_vm::<u8>(&args, &program, &passes),
+        CellSize::U16 => run_vm::<u16>(&args, &program, &passes),
+        CellSize::U32 => run_vm::<u32>(&args, &program, &passes),
+        CellSize::U64 => run_vm::<u64>(&args, &program, &passes),
+        CellSize::I8 => run_vm::<i8>(&args, &program, &passes),
+        CellSize::I16 => run_vm::<i16>(&args, &program, &passes),
+        CellSize::I32 => run_vm::<i32>(&args, &program, &passes),
+        CellSize::I64 => run_vm::<i64>(&args, &program, &passes),
+    }
+}
+
+/// Interprets `program` on a [VM] whose cells are of type T.
+fn run_vm<T: CellKind>(
+    args: &cli::Args,
+    program: &ValidatedProgram,
+    passes: &PassManager,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut virtual_machine: VM<T> = VM::new(args.cells, args.extensible, program)
+        .with_overflow_policy(args.overflow_policy)
+        .with_passes(passes)
+        .with_topology(args.topology.unwrap_or(if args.extensible {
+            Topology::Extensible
+        } else {
+            Topology::Bounded
+        }))
+        .with_backend(args.backend)
+        .with_memory_limit(args.max_memory)
+        .with_max_cells(args.max_cells)
+        .with_growth_policy(args.growth_policy)
+        .with_max_steps(args.max_steps)
+        .with_flush_policy(args.flush_policy)
+        .with_eof_policy(args.eof_policy)
+        .with_state_context(Some(args.context_cells));
+    if let Some(timeout) = args.timeout {
+        let token = CancellationToken::new();
+        virtual_machine = virtual_machine.with_cancellation(token.clone());
+        thread::spawn(move || {
+            thread::sleep(timeout);
+            token.cancel();
+        });
+    }
+    virtual_machine
+        .interpret(&mut stdin(), &mut stdout())
+        .map_err(|error| {
+            let diagnostic = virtual_machine.diagnostic(&error);
+            render_errors(args, [(error.snippet(program), diagnostic)])
+        })?;
     Ok(())
 }
 
+/// Renders `errors` in the format asked for by `args`, either as their [Snippet]s or as their [Diagnostic]s, one JSON
+/// object per line.
+fn render_errors<'a>(
+    args: &cli::Args,
+    errors: impl IntoIterator<Item = (Snippet<'a>, Diagnostic)>,
+) -> String {
+    let color = args.color.enabled();
+    let rendered: Vec<String> = errors
+        .into_iter()
+        .map(|(snippet, diagnostic)| match args.error_format {
+            ErrorFormat::Human => snippet.with_color(color).to_string(),
+            ErrorFormat::Json => to_json(&diagnostic),
+        })
+        .collect();
+    match args.error_format {
+        ErrorFormat::Human => rendered.join("\n\n"),
+        ErrorFormat::Json => rendered.join("\n"),
+    }
+}
+
+/// Serializes `diagnostic` into a single line of JSON.
+fn to_json(diagnostic: &Diagnostic) -> String {
+    serde_json::to_string(diagnostic).expect("a diagnostic is always serializable")
+}
+
 /// The entry point for Brainfuck Interpreter. The program has a modern CLI, which contains everything you should know.
 /// # Example:
 /// ```shell